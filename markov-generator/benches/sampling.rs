@@ -0,0 +1,50 @@
+//! Compares the cumulative and alias-table sampling methods on a dense chain.
+//!
+//! Run with `cargo bench --bench sampling`.
+
+use markov_generator::markov_chain::{edge::Edge, node::Node, MarkovChain, SamplingMethod};
+use std::hint::black_box;
+use std::time::Instant;
+
+const NODES: u32 = 1_000;
+const EDGES_PER_NODE: u32 = 200;
+const STEPS: u32 = 1_000_000;
+
+fn build_chain() -> MarkovChain {
+    let mut mc = MarkovChain::new(None, None);
+    for id in 0..NODES {
+        mc.add_node(Node::new(id, None));
+        for k in 0..EDGES_PER_NODE {
+            let to = (id * 7 + k * 13) % NODES;
+            mc.add_edge(Edge::new(id, to, 1.0 + (k % 5) as f32));
+        }
+    }
+    mc
+}
+
+fn bench(method: SamplingMethod) {
+    let mut mc = build_chain();
+    mc.set_sampling_method(method);
+    mc.set_current_node(0).unwrap();
+
+    let start = Instant::now();
+    for _ in 0..STEPS {
+        mc.next().unwrap();
+        black_box(mc.get_current_node());
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{:?}: {} steps over {} edges/node in {:?} ({:.1} ns/step)",
+        method,
+        STEPS,
+        EDGES_PER_NODE,
+        elapsed,
+        elapsed.as_nanos() as f64 / STEPS as f64
+    );
+}
+
+fn main() {
+    bench(SamplingMethod::Cumulative);
+    bench(SamplingMethod::Alias);
+}
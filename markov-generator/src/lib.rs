@@ -0,0 +1 @@
+pub mod markov_chain;
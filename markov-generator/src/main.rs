@@ -1,9 +1,9 @@
-mod markov_chain;
-use crate::markov_chain::{action::Action, edge::Edge, node::Node, MarkovChain};
-use serde_json;
+use markov_generator::markov_chain::{
+    action::Action, edge::Edge, node::Node, MarkovChain, MarkovChainError,
+};
 use serde_json::json;
 
-fn main() {
+fn run() -> Result<(), MarkovChainError> {
     let mut mc = MarkovChain::new(None, None);
     let n1 = Node::new(1, None);
     let n2 = Node::new(2, None);
@@ -22,7 +22,20 @@ fn main() {
     mc.add_edge(Edge::new(3, 1, 0.8));
     mc.add_edge(Edge::new(3, 2, 0.4));
 
-    let serialized = serde_json::to_string(&mc).unwrap();
+    match std::env::args().nth(1).as_deref() {
+        Some("classes") => println!("{}", mc.communicating_classes()?),
+        _ => println!(
+            "{}",
+            serde_json::to_string(&mc).map_err(MarkovChainError::SerializationError)?
+        ),
+    }
+
+    Ok(())
+}
 
-    println!("{}", serialized);
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,243 @@
+use crate::markov_chain::{
+    hitting::backwards_closure, linalg::solve_columns, MarkovChain, MarkovChainError,
+};
+use std::collections::HashMap;
+
+/// The fundamental-matrix analysis of an absorbing chain, created by
+/// [`MarkovChain::absorbing_analysis`].
+///
+/// With the transition matrix split into transient-to-transient `Q` and
+/// transient-to-absorbing `R` blocks, the fundamental matrix is
+/// `N = (I - Q)⁻¹`. `N[i][j]` is the expected number of visits to transient
+/// node `j` starting from transient node `i`, `N·1` gives the expected number
+/// of steps until absorption, and `N·R` the probability of ending up in each
+/// absorbing node.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AbsorbingAnalysis {
+    transient: Vec<u32>,
+    absorbing: Vec<u32>,
+    transient_index: HashMap<u32, usize>,
+    absorbing_index: HashMap<u32, usize>,
+    fundamental: Vec<Vec<f64>>,
+    expected_steps: Vec<f64>,
+    absorption: Vec<Vec<f64>>,
+}
+
+impl AbsorbingAnalysis {
+    /// Transient node ids in ascending order; the rows and columns of the
+    /// fundamental matrix.
+    pub fn transient_nodes(&self) -> &[u32] {
+        &self.transient
+    }
+
+    /// Absorbing node ids in ascending order.
+    pub fn absorbing_nodes(&self) -> &[u32] {
+        &self.absorbing
+    }
+
+    pub fn fundamental_matrix(&self) -> &[Vec<f64>] {
+        &self.fundamental
+    }
+
+    /// The expected number of steps until absorption starting from `node_id`,
+    /// which is zero for absorbing nodes.
+    pub fn expected_steps(&self, node_id: u32) -> Option<f64> {
+        if self.absorbing_index.contains_key(&node_id) {
+            return Some(0.0);
+        }
+        self.transient_index
+            .get(&node_id)
+            .map(|&i| self.expected_steps[i])
+    }
+
+    /// The probability that a walk from `from` is absorbed in `to`.
+    pub fn absorption_probability(&self, from: u32, to: u32) -> Option<f64> {
+        let j = *self.absorbing_index.get(&to)?;
+        if self.absorbing_index.contains_key(&from) {
+            return Some(if from == to { 1.0 } else { 0.0 });
+        }
+        self.transient_index
+            .get(&from)
+            .map(|&i| self.absorption[i][j])
+    }
+
+    /// The absorption probabilities from `from`, keyed by absorbing node id.
+    pub fn absorption_probabilities(&self, from: u32) -> Option<HashMap<u32, f64>> {
+        self.absorbing
+            .iter()
+            .map(|&to| Some((to, self.absorption_probability(from, to)?)))
+            .collect()
+    }
+
+    /// The expected number of visits to transient node `to` before
+    /// absorption, starting from transient node `from`. The start counts as
+    /// a visit.
+    pub fn expected_visits(&self, from: u32, to: u32) -> Option<f64> {
+        let i = *self.transient_index.get(&from)?;
+        let j = *self.transient_index.get(&to)?;
+        Some(self.fundamental[i][j])
+    }
+}
+
+impl MarkovChain {
+    /// Analyses the chain as an absorbing chain. Nodes without outgoing
+    /// edges, nodes whose only transition is a self-loop, and terminal nodes
+    /// are absorbing; all other nodes are transient.
+    ///
+    /// Fails with `NotAbsorbingError` if some transient node cannot reach an
+    /// absorbing node, since the walk would then never end from there.
+    pub fn absorbing_analysis(&self) -> Result<AbsorbingAnalysis, MarkovChainError> {
+        let mut matrix = self.transition_matrix()?;
+        matrix.absorb_terminal_nodes(self);
+        let n = matrix.len();
+
+        let is_absorbing: Vec<bool> = (0..n)
+            .map(|i| matrix.row(i).iter().all(|&(j, _)| j == i))
+            .collect();
+
+        // Walk backwards from the absorbing nodes to find who can reach them.
+        let mut predecessors = vec![Vec::new(); n];
+        for i in (0..n).filter(|&i| !is_absorbing[i]) {
+            for &(j, _) in matrix.row(i) {
+                predecessors[j].push(i);
+            }
+        }
+        let reaches = backwards_closure(&predecessors, &is_absorbing);
+        let stuck: Vec<u32> = (0..n)
+            .filter(|&i| !reaches[i])
+            .map(|i| matrix.node_ids()[i])
+            .collect();
+        if !stuck.is_empty() {
+            return Err(MarkovChainError::NotAbsorbingError { node_ids: stuck });
+        }
+
+        let (absorbing_rows, transient_rows): (Vec<usize>, Vec<usize>) =
+            (0..n).partition(|&i| is_absorbing[i]);
+        let mut transient_position = vec![None; n];
+        let mut absorbing_position = vec![None; n];
+        for (k, &i) in transient_rows.iter().enumerate() {
+            transient_position[i] = Some(k);
+        }
+        for (k, &i) in absorbing_rows.iter().enumerate() {
+            absorbing_position[i] = Some(k);
+        }
+
+        let t = transient_rows.len();
+        let mut i_minus_q = vec![vec![0.0; t]; t];
+        let mut r = vec![vec![0.0; absorbing_rows.len()]; t];
+        for (k, &i) in transient_rows.iter().enumerate() {
+            i_minus_q[k][k] += 1.0;
+            for &(j, p) in matrix.row(i) {
+                match (transient_position[j], absorbing_position[j]) {
+                    (Some(l), _) => i_minus_q[k][l] -= p,
+                    (_, Some(l)) => r[k][l] += p,
+                    _ => unreachable!(),
+                }
+            }
+        }
+
+        let identity = (0..t)
+            .map(|k| (0..t).map(|l| if k == l { 1.0 } else { 0.0 }).collect())
+            .collect();
+        let fundamental =
+            solve_columns(i_minus_q, identity).ok_or(MarkovChainError::SingularMatrixError)?;
+
+        let expected_steps = fundamental.iter().map(|row| row.iter().sum()).collect();
+        let absorption = fundamental
+            .iter()
+            .map(|row| {
+                (0..absorbing_rows.len())
+                    .map(|l| row.iter().zip(&r).map(|(n, r)| n * r[l]).sum())
+                    .collect()
+            })
+            .collect();
+
+        let ids =
+            |rows: &[usize]| -> Vec<u32> { rows.iter().map(|&i| matrix.node_ids()[i]).collect() };
+        let index = |ids: &[u32]| -> HashMap<u32, usize> {
+            ids.iter().enumerate().map(|(k, &id)| (id, k)).collect()
+        };
+        let transient = ids(&transient_rows);
+        let absorbing = ids(&absorbing_rows);
+
+        Ok(AbsorbingAnalysis {
+            transient_index: index(&transient),
+            absorbing_index: index(&absorbing),
+            transient,
+            absorbing,
+            fundamental,
+            expected_steps,
+            absorption,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markov_chain::{edge::Edge, node::Node};
+
+    /// A symmetric random walk on 0..=4 that stops at either end.
+    fn gamblers_ruin() -> MarkovChain {
+        let mut mc = MarkovChain::new(None, None);
+        for id in 0..=4 {
+            mc.add_node(Node::new(id, None));
+        }
+        for id in 1..=3 {
+            mc.add_edge(Edge::new(id, id - 1, 1.0));
+            mc.add_edge(Edge::new(id, id + 1, 1.0));
+        }
+        mc
+    }
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        assert!((actual.unwrap() - expected).abs() < 1e-9, "{:?}", actual);
+    }
+
+    #[test]
+    fn test_absorbing_analysis() {
+        let analysis = gamblers_ruin().absorbing_analysis().unwrap();
+
+        assert_eq!(analysis.transient_nodes(), &[1, 2, 3]);
+        assert_eq!(analysis.absorbing_nodes(), &[0, 4]);
+
+        assert_close(analysis.expected_steps(1), 3.0);
+        assert_close(analysis.expected_steps(2), 4.0);
+        assert_close(analysis.expected_steps(4), 0.0);
+
+        assert_close(analysis.absorption_probability(1, 0), 0.75);
+        assert_close(analysis.absorption_probability(1, 4), 0.25);
+        assert_close(analysis.absorption_probability(0, 0), 1.0);
+        assert_eq!(analysis.absorption_probability(1, 2), None);
+
+        assert_close(analysis.expected_visits(2, 2), 2.0);
+        assert_close(analysis.expected_visits(1, 3), 0.5);
+    }
+
+    #[test]
+    fn test_terminal_nodes_are_absorbing() {
+        let mut mc = gamblers_ruin();
+        mc.add_terminal_node(2);
+        let analysis = mc.absorbing_analysis().unwrap();
+
+        assert_eq!(analysis.absorbing_nodes(), &[0, 2, 4]);
+        assert_close(analysis.expected_steps(1), 1.0);
+        assert_close(analysis.absorption_probability(3, 2), 0.5);
+    }
+
+    #[test]
+    fn test_not_absorbing() {
+        let mut mc = gamblers_ruin();
+        mc.add_node(Node::new(5, None));
+        mc.add_node(Node::new(6, None));
+        mc.add_edge(Edge::new(5, 6, 1.0));
+        mc.add_edge(Edge::new(6, 5, 1.0));
+
+        assert_eq!(
+            mc.absorbing_analysis(),
+            Err(MarkovChainError::NotAbsorbingError {
+                node_ids: vec![5, 6]
+            })
+        );
+    }
+}
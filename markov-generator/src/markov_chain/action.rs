@@ -1,14 +1,55 @@
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
 use std::str::FromStr;
 
+/// An action with a payload of type `V`. Most code uses the [`Action`] alias,
+/// whose payload is any JSON value.
 #[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
-pub struct Action {
+pub struct GenericAction<V> {
     pub id: u32,
-    pub value: Value,
+    pub value: V,
+    /// How likely this action is to be picked under
+    /// [`ActionSelection::Weighted`](crate::markov_chain::node::ActionSelection::Weighted).
+    #[serde(default = "default_weight", skip_serializing_if = "is_default_weight")]
+    pub weight: f32,
+    /// When the action fires.
+    #[serde(default, skip_serializing_if = "Trigger::is_enter")]
+    pub trigger: Trigger,
 }
 
-impl FromStr for Action {
+pub type Action = GenericAction<Value>;
+
+/// Which transitions make an action fire.
+///
+/// A step between two different nodes fires the old node's `Exit` actions
+/// and then the new node's `Enter` actions. A self-loop fires neither, only
+/// the node's `Stay` actions.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    #[default]
+    Enter,
+    Exit,
+    Stay,
+}
+
+impl Trigger {
+    fn is_enter(&self) -> bool {
+        *self == Trigger::Enter
+    }
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+fn is_default_weight(weight: &f32) -> bool {
+    *weight == default_weight()
+}
+
+impl<V: DeserializeOwned> FromStr for GenericAction<V> {
     type Err = serde_json::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -17,12 +58,39 @@ impl FromStr for Action {
 }
 
 impl Action {
-    pub fn new(id: u32, value: Option<Value>) -> Action {
+    /// Decodes the value as a `T`.
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        T::deserialize(&self.value)
+    }
+}
+
+impl<V: Default> GenericAction<V> {
+    #[allow(clippy::manual_unwrap_or_default)]
+    pub fn new(id: u32, value: Option<V>) -> GenericAction<V> {
         let _value = match value {
             Some(value) => value,
-            None => Value::Null,
+            None => V::default(),
         };
 
-        Action { id, value: _value }
+        GenericAction {
+            id,
+            value: _value,
+            weight: default_weight(),
+            trigger: Trigger::Enter,
+        }
+    }
+
+    pub fn weighted(id: u32, value: Option<V>, weight: f32) -> GenericAction<V> {
+        GenericAction {
+            weight,
+            ..GenericAction::new(id, value)
+        }
+    }
+
+    pub fn triggered(id: u32, value: Option<V>, trigger: Trigger) -> GenericAction<V> {
+        GenericAction {
+            trigger,
+            ..GenericAction::new(id, value)
+        }
     }
 }
@@ -0,0 +1,94 @@
+use rand::Rng;
+
+/// A Walker/Vose alias table for sampling an index from a fixed set of
+/// weights in constant time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AliasTable {
+    prob: Vec<f64>,
+    alias: Vec<usize>,
+}
+
+impl AliasTable {
+    /// Builds a table for `weights`. Returns `None` if there are no weights or
+    /// they do not sum to a positive, finite total.
+    pub fn new(weights: &[f32]) -> Option<AliasTable> {
+        let n = weights.len();
+        let total: f64 = weights.iter().map(|&weight| weight as f64).sum();
+        if n == 0 || !total.is_finite() || total <= 0.0 {
+            return None;
+        }
+
+        let mut scaled: Vec<f64> = weights
+            .iter()
+            .map(|&weight| weight as f64 * n as f64 / total)
+            .collect();
+        let mut prob = vec![1.0; n];
+        let mut alias: Vec<usize> = (0..n).collect();
+
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) =
+            (0..n).partition(|&i| scaled[i] < 1.0);
+
+        while let (Some(l), Some(g)) = (small.pop(), large.pop()) {
+            prob[l] = scaled[l];
+            alias[l] = g;
+            scaled[g] = (scaled[g] + scaled[l]) - 1.0;
+            if scaled[g] < 1.0 {
+                small.push(g);
+            } else {
+                large.push(g);
+            }
+        }
+
+        // Whatever is left over is only off from 1.0 by rounding error.
+        for i in small.into_iter().chain(large) {
+            prob[i] = 1.0;
+        }
+
+        Some(AliasTable { prob, alias })
+    }
+
+    pub fn len(&self) -> usize {
+        self.prob.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prob.is_empty()
+    }
+
+    /// Draws an index into the weights the table was built from.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        let i = rng.gen_range(0..self.prob.len());
+        if rng.gen::<f64>() < self.prob[i] {
+            i
+        } else {
+            self.alias[i]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alias_table_probabilities() {
+        let table = AliasTable::new(&[1.0, 2.0, 1.0]).unwrap();
+
+        // Each column i is kept with prob[i] and otherwise gives way to alias[i].
+        let mut mass = vec![0.0; table.len()];
+        for i in 0..table.len() {
+            mass[i] += table.prob[i] / table.len() as f64;
+            mass[table.alias[i]] += (1.0 - table.prob[i]) / table.len() as f64;
+        }
+
+        for (actual, expected) in mass.iter().zip([0.25, 0.5, 0.25]) {
+            assert!((actual - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_alias_table_rejects_empty_weights() {
+        assert_eq!(AliasTable::new(&[]), None);
+        assert_eq!(AliasTable::new(&[0.0, 0.0]), None);
+    }
+}
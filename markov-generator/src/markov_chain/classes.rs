@@ -0,0 +1,297 @@
+use crate::markov_chain::{transition_matrix::TransitionMatrix, MarkovChain, MarkovChainError};
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClassKind {
+    /// The walk eventually leaves the class for good.
+    Transient,
+    /// The class is closed: once entered, the walk never leaves.
+    Recurrent,
+}
+
+/// A maximal set of nodes that can all reach each other.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CommunicatingClass {
+    /// Node ids in ascending order.
+    pub node_ids: Vec<u32>,
+    pub kind: ClassKind,
+    /// The gcd of the lengths of the cycles through the class, or `None` for
+    /// a single node that lies on no cycle.
+    pub period: Option<usize>,
+}
+
+impl CommunicatingClass {
+    pub fn is_recurrent(&self) -> bool {
+        self.kind == ClassKind::Recurrent
+    }
+}
+
+/// The communicating classes of a chain and what they say about it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ClassReport {
+    /// Classes ordered by their smallest node id.
+    pub classes: Vec<CommunicatingClass>,
+    /// Every node can reach every other node.
+    pub irreducible: bool,
+    /// Irreducible and aperiodic, so the walk converges to a unique
+    /// stationary distribution from any start.
+    pub ergodic: bool,
+}
+
+impl ClassReport {
+    pub fn recurrent_classes(&self) -> impl Iterator<Item = &CommunicatingClass> {
+        self.classes.iter().filter(|class| class.is_recurrent())
+    }
+
+    pub fn transient_classes(&self) -> impl Iterator<Item = &CommunicatingClass> {
+        self.classes.iter().filter(|class| !class.is_recurrent())
+    }
+}
+
+impl fmt::Display for ClassReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} communicating class(es); {}, {}",
+            self.classes.len(),
+            if self.irreducible {
+                "irreducible"
+            } else {
+                "reducible"
+            },
+            if self.ergodic {
+                "ergodic"
+            } else {
+                "not ergodic"
+            },
+        )?;
+
+        for class in self.classes.iter() {
+            let kind = match class.kind {
+                ClassKind::Transient => "transient",
+                ClassKind::Recurrent => "recurrent",
+            };
+            write!(f, "\n  {} class {:?}", kind, class.node_ids)?;
+            match class.period {
+                Some(period) => write!(f, ", period {}", period)?,
+                None => write!(f, ", no cycles")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl MarkovChain {
+    /// Splits the chain into communicating classes and classifies each one.
+    ///
+    /// Like the other analyses this works on the [`TransitionMatrix`], so
+    /// nodes without outgoing edges count as absorbing recurrent classes and
+    /// zero-weight edges are ignored.
+    pub fn communicating_classes(&self) -> Result<ClassReport, MarkovChainError> {
+        let matrix = self.transition_matrix()?;
+
+        let classes: Vec<CommunicatingClass> = strongly_connected_components(&matrix)
+            .into_iter()
+            .map(|component| CommunicatingClass {
+                node_ids: component.iter().map(|&i| matrix.node_ids()[i]).collect(),
+                kind: if is_closed(&matrix, &component) {
+                    ClassKind::Recurrent
+                } else {
+                    ClassKind::Transient
+                },
+                period: Some(period(&matrix, &component)).filter(|&period| period > 0),
+            })
+            .collect();
+
+        let irreducible = classes.len() == 1;
+        let ergodic = irreducible && classes[0].period == Some(1);
+
+        Ok(ClassReport {
+            classes,
+            irreducible,
+            ergodic,
+        })
+    }
+}
+
+/// Finds the strongly connected components of the matrix's transition graph
+/// with an iterative Tarjan's algorithm. Each component lists row indices in
+/// ascending order, and components are ordered by their first index.
+pub(crate) fn strongly_connected_components(matrix: &TransitionMatrix) -> Vec<Vec<usize>> {
+    const UNVISITED: usize = usize::MAX;
+
+    let n = matrix.len();
+    let mut index = vec![UNVISITED; n];
+    let mut low = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = Vec::new();
+    let mut components = Vec::new();
+    let mut next_index = 0;
+
+    for root in 0..n {
+        if index[root] != UNVISITED {
+            continue;
+        }
+
+        // Each frame is a node and the position of the next edge to explore.
+        let mut frames = vec![(root, 0)];
+        index[root] = next_index;
+        low[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some(&(v, position)) = frames.last() {
+            if let Some(&(w, _)) = matrix.row(v).get(position) {
+                frames.last_mut().unwrap().1 += 1;
+                if index[w] == UNVISITED {
+                    index[w] = next_index;
+                    low[w] = next_index;
+                    next_index += 1;
+                    stack.push(w);
+                    on_stack[w] = true;
+                    frames.push((w, 0));
+                } else if on_stack[w] {
+                    low[v] = low[v].min(index[w]);
+                }
+                continue;
+            }
+
+            frames.pop();
+            if let Some(&(parent, _)) = frames.last() {
+                low[parent] = low[parent].min(low[v]);
+            }
+
+            if low[v] == index[v] {
+                let mut component = Vec::new();
+                while let Some(w) = stack.pop() {
+                    on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                component.sort();
+                components.push(component);
+            }
+        }
+    }
+
+    components.sort_by_key(|component| component[0]);
+    components
+}
+
+/// Whether no transition leaves `component`. In a finite chain the closed
+/// classes are exactly the recurrent ones.
+pub(crate) fn is_closed(matrix: &TransitionMatrix, component: &[usize]) -> bool {
+    component.iter().all(|&i| {
+        matrix
+            .row(i)
+            .iter()
+            .all(|(j, _)| component.binary_search(j).is_ok())
+    })
+}
+
+/// The period of a strongly connected `component`: the gcd of the lengths of
+/// all cycles through it. Returns 0 for a single node without a self-loop,
+/// which lies on no cycle at all.
+pub(crate) fn period(matrix: &TransitionMatrix, component: &[usize]) -> usize {
+    let mut level = vec![None; matrix.len()];
+    level[component[0]] = Some(0usize);
+    let mut queue = std::collections::VecDeque::from([component[0]]);
+    let mut period = 0;
+
+    while let Some(i) = queue.pop_front() {
+        let depth = level[i].unwrap();
+        for &(j, _) in matrix.row(i) {
+            if component.binary_search(&j).is_err() {
+                continue;
+            }
+            match level[j] {
+                None => {
+                    level[j] = Some(depth + 1);
+                    queue.push_back(j);
+                }
+                Some(other) => period = gcd(period, (depth + 1).abs_diff(other)),
+            }
+        }
+    }
+
+    period
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// The closed classes of the matrix, as returned by
+/// [`strongly_connected_components`].
+pub(crate) fn recurrent_classes(matrix: &TransitionMatrix) -> Vec<Vec<usize>> {
+    strongly_connected_components(matrix)
+        .into_iter()
+        .filter(|component| is_closed(matrix, component))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markov_chain::test_util::chain;
+
+    #[test]
+    fn test_communicating_classes() {
+        // 1 is transient, {2, 3} is a periodic trap and 4 is a sink.
+        let mc = chain(
+            &[1, 2, 3, 4],
+            &[(1, 2, 1.0), (1, 4, 1.0), (2, 3, 1.0), (3, 2, 1.0)],
+        );
+        let report = mc.communicating_classes().unwrap();
+
+        assert_eq!(
+            report.classes,
+            vec![
+                CommunicatingClass {
+                    node_ids: vec![1],
+                    kind: ClassKind::Transient,
+                    period: None,
+                },
+                CommunicatingClass {
+                    node_ids: vec![2, 3],
+                    kind: ClassKind::Recurrent,
+                    period: Some(2),
+                },
+                CommunicatingClass {
+                    node_ids: vec![4],
+                    kind: ClassKind::Recurrent,
+                    period: Some(1),
+                },
+            ]
+        );
+        assert!(!report.irreducible);
+        assert!(!report.ergodic);
+        assert_eq!(report.recurrent_classes().count(), 2);
+    }
+
+    #[test]
+    fn test_ergodic_chain() {
+        let mc = chain(
+            &[1, 2, 3],
+            &[(1, 2, 1.0), (2, 3, 1.0), (3, 1, 1.0), (3, 2, 1.0)],
+        );
+        let report = mc.communicating_classes().unwrap();
+
+        assert!(report.irreducible);
+        assert!(report.ergodic);
+        assert_eq!(
+            report.to_string(),
+            "1 communicating class(es); irreducible, ergodic\n  recurrent class [1, 2, 3], period 1"
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::markov_chain::{MarkovChain, MarkovChainError};
+use std::collections::HashMap;
+
+/// The expectation of a numeric action value under a distribution over
+/// nodes, as computed by [`MarkovChain::expected_action_value`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ActionExpectation {
+    /// `Σ p(node) · value(node)`, where nodes without a numeric value count
+    /// as zero.
+    pub value: f64,
+    /// The probability mass of the nodes that have a numeric value. Divide
+    /// `value` by it to condition on the action being present.
+    pub mass: f64,
+}
+
+impl MarkovChain {
+    /// Steps a distribution over nodes forward `steps` times through the
+    /// normalized edge weights and returns it after every step, starting with
+    /// `initial` itself.
+    ///
+    /// `initial` maps node ids to non-negative probabilities; nodes not
+    /// listed start at zero. It is not normalized, and its total mass is
+    /// preserved. Mass on a terminal node or a node without outgoing edges
+    /// stays there, as a walk would.
+    pub fn evolve_distribution(
+        &self,
+        initial: &HashMap<u32, f64>,
+        steps: usize,
+    ) -> Result<Vec<HashMap<u32, f64>>, MarkovChainError> {
+        let mut matrix = self.transition_matrix()?;
+        matrix.absorb_terminal_nodes(self);
+
+        let mut distribution = vec![0.0; matrix.len()];
+        for (&node_id, &probability) in initial.iter() {
+            let i = matrix
+                .index_of(node_id)
+                .ok_or(MarkovChainError::NodeDoesNotExistError { node_id })?;
+            if !probability.is_finite() || probability < 0.0 {
+                return Err(MarkovChainError::InvalidProbabilityError {
+                    node_id,
+                    probability,
+                });
+            }
+            distribution[i] = probability;
+        }
+
+        let mut distributions = Vec::with_capacity(steps + 1);
+        distributions.push(matrix.to_map(&distribution));
+        for _ in 0..steps {
+            distribution = matrix.step(&distribution);
+            distributions.push(matrix.to_map(&distribution));
+        }
+
+        Ok(distributions)
+    }
+
+    /// Like [`MarkovChain::evolve_distribution`], starting with all mass on
+    /// `node_id`.
+    pub fn evolve_from_node(
+        &self,
+        node_id: u32,
+        steps: usize,
+    ) -> Result<Vec<HashMap<u32, f64>>, MarkovChainError> {
+        self.evolve_distribution(&HashMap::from([(node_id, 1.0)]), steps)
+    }
+
+    /// The expected value of the numeric field at `pointer` (a JSON pointer
+    /// such as `"/volume"`, or `""` for the value itself) of action
+    /// `action_id` under `distribution`.
+    pub fn expected_action_value(
+        &self,
+        distribution: &HashMap<u32, f64>,
+        action_id: u32,
+        pointer: &str,
+    ) -> ActionExpectation {
+        let mut expectation = ActionExpectation {
+            value: 0.0,
+            mass: 0.0,
+        };
+
+        for (&node_id, &probability) in distribution.iter() {
+            let value = self
+                .get_node_action(node_id, action_id)
+                .and_then(|action| action.value.pointer(pointer))
+                .and_then(|value| value.as_f64());
+            if let Some(value) = value {
+                expectation.value += probability * value;
+                expectation.mass += probability;
+            }
+        }
+
+        expectation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markov_chain::{action::Action, edge::Edge, node::Node};
+    use serde_json::json;
+
+    fn create_chain() -> MarkovChain {
+        let nodes = vec![
+            Node::new(
+                1,
+                Some(vec![Action::new(10, Some(json!({ "volume": 2.0 })))]),
+            ),
+            Node::new(
+                2,
+                Some(vec![Action::new(10, Some(json!({ "volume": 6.0 })))]),
+            ),
+            Node::new(3, None),
+        ];
+        let edges = vec![
+            Edge::new(1, 2, 1.0),
+            Edge::new(1, 3, 1.0),
+            Edge::new(2, 1, 1.0),
+        ];
+        MarkovChain::new(Some(nodes), Some(edges))
+    }
+
+    #[test]
+    fn test_evolve_from_node() {
+        let distributions = create_chain().evolve_from_node(1, 3).unwrap();
+
+        assert_eq!(distributions.len(), 4);
+        assert_eq!(
+            distributions[0],
+            HashMap::from([(1, 1.0), (2, 0.0), (3, 0.0)])
+        );
+        assert_eq!(
+            distributions[1],
+            HashMap::from([(1, 0.0), (2, 0.5), (3, 0.5)])
+        );
+        assert_eq!(
+            distributions[2],
+            HashMap::from([(1, 0.5), (2, 0.0), (3, 0.5)])
+        );
+        assert_eq!(
+            distributions[3],
+            HashMap::from([(1, 0.0), (2, 0.25), (3, 0.75)])
+        );
+    }
+
+    #[test]
+    fn test_evolve_distribution_rejects_bad_input() {
+        let mc = create_chain();
+        assert_eq!(
+            mc.evolve_distribution(&HashMap::from([(9, 1.0)]), 1),
+            Err(MarkovChainError::NodeDoesNotExistError { node_id: 9 })
+        );
+        assert_eq!(
+            mc.evolve_distribution(&HashMap::from([(1, -0.5)]), 1),
+            Err(MarkovChainError::InvalidProbabilityError {
+                node_id: 1,
+                probability: -0.5
+            })
+        );
+    }
+
+    #[test]
+    fn test_expected_action_value() {
+        let mc = create_chain();
+        let distribution = HashMap::from([(1, 0.25), (2, 0.25), (3, 0.5)]);
+
+        assert_eq!(
+            mc.expected_action_value(&distribution, 10, "/volume"),
+            ActionExpectation {
+                value: 2.0,
+                mass: 0.5
+            }
+        );
+        assert_eq!(mc.expected_action_value(&distribution, 10, "").mass, 0.0);
+    }
+}
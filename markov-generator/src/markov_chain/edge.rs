@@ -1,14 +1,45 @@
+use crate::markov_chain::action::GenericAction;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
 
-#[derive(Clone, Serialize, Deserialize, PartialEq, PartialOrd, Debug)]
-pub struct Edge {
-    pub from: u32,
-    pub to: u32,
+/// A weighted edge between node ids of type `I`, whose actions have payloads
+/// of type `V`. Most code uses the [`Edge`] alias.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct GenericEdge<I, V> {
+    pub from: I,
+    pub to: I,
     pub weight: f32,
+    /// Actions that fire when a walk takes this edge.
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<GenericAction<V>>,
 }
 
-impl Edge {
-    pub fn new(from: u32, to: u32, weight: f32) -> Edge {
-        Edge { from, to, weight }
+pub type Edge = GenericEdge<u32, Value>;
+
+impl<I, V> GenericEdge<I, V> {
+    pub fn new(from: I, to: I, weight: f32) -> GenericEdge<I, V> {
+        GenericEdge {
+            from,
+            to,
+            weight,
+            actions: Vec::new(),
+        }
+    }
+}
+
+/// Edges are ordered by `from`, `to` and then weight. Edges that agree on all
+/// three but have different actions are incomparable.
+impl<I: PartialOrd, V: PartialEq> PartialOrd for GenericEdge<I, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let ordering = (&self.from, &self.to, self.weight).partial_cmp(&(
+            &other.from,
+            &other.to,
+            other.weight,
+        ))?;
+        match ordering {
+            Ordering::Equal if self.actions != other.actions => None,
+            ordering => Some(ordering),
+        }
     }
 }
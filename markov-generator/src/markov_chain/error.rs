@@ -0,0 +1,358 @@
+use crate::markov_chain::validation::Diagnostic;
+use std::error::Error;
+use std::fmt;
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug)]
+/// Errors returned by chain operations. `I` is the node id type, so the
+/// default matches [`MarkovChain`](crate::markov_chain::MarkovChain).
+pub enum MarkovChainError<I = u32> {
+    /// The chain has no current node to step from.
+    NoCurrentNodeError,
+    NodeDoesNotExistError {
+        node_id: I,
+    },
+    EdgeDoesNotExistError {
+        from: I,
+        to: I,
+    },
+    ActionDoesNotExistError {
+        node_id: I,
+        action_id: u32,
+    },
+    NodeHasNoEdgesError {
+        node_id: I,
+    },
+    /// The node has outgoing edges, but their weights cannot be sampled.
+    TransitionFailedError {
+        node_id: I,
+    },
+    NodeAlreadyExistsError {
+        node_id: I,
+    },
+    EdgeAlreadyExistsError {
+        from: I,
+        to: I,
+    },
+    ActionAlreadyExistsError {
+        node_id: I,
+        action_id: u32,
+    },
+    EdgeActionAlreadyExistsError {
+        from: I,
+        to: I,
+        action_id: u32,
+    },
+    InvalidWeightError {
+        from: I,
+        to: I,
+        weight: f32,
+    },
+    /// The chain has more than one recurrent class, listed by node id.
+    ReducibleChainError {
+        recurrent_classes: Vec<Vec<I>>,
+    },
+    PeriodicChainError {
+        period: usize,
+    },
+    NotConvergedError {
+        iterations: usize,
+    },
+    SingularMatrixError,
+    /// A context for an order-`order` chain does not have `order` ids.
+    InvalidContextError {
+        order: usize,
+        context: Vec<I>,
+    },
+    /// A higher-order chain has no transitions out of this context.
+    UnknownContextError {
+        context: Vec<I>,
+    },
+    InvalidProbabilityError {
+        node_id: I,
+        probability: f64,
+    },
+    /// A tuning parameter, such as a smoothing or decay factor, is out of
+    /// range.
+    InvalidParameterError {
+        name: &'static str,
+        value: f64,
+        expected: &'static str,
+    },
+    /// These nodes cannot reach an absorbing node.
+    NotAbsorbingError {
+        node_ids: Vec<I>,
+    },
+    NameAlreadyExistsError {
+        name: String,
+    },
+    /// Every node id is already in use.
+    NoFreeNodeIdError,
+    UnknownNameError {
+        name: String,
+    },
+    /// A token is not in the text chain's vocabulary.
+    UnknownTokenError {
+        token: String,
+    },
+    /// An action value could not be decoded as the requested type.
+    ActionDecodeError {
+        node_id: I,
+        action_id: u32,
+        error: serde_json::Error,
+    },
+    DeserializationError(serde_json::Error),
+    SerializationError(serde_json::Error),
+    InvalidChainError(Vec<Diagnostic>),
+}
+
+impl<I: fmt::Debug> fmt::Display for MarkovChainError<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use MarkovChainError::*;
+
+        match self {
+            NoCurrentNodeError => write!(f, "the chain has no current node"),
+            NodeDoesNotExistError { node_id } => write!(f, "node {:?} does not exist", node_id),
+            EdgeDoesNotExistError { from, to } => {
+                write!(f, "edge {:?} -> {:?} does not exist", from, to)
+            }
+            ActionDoesNotExistError { node_id, action_id } => {
+                write!(f, "action {} does not exist on node {:?}", action_id, node_id)
+            }
+            NodeHasNoEdgesError { node_id } => {
+                write!(f, "node {:?} has no outgoing edges", node_id)
+            }
+            TransitionFailedError { node_id } => write!(
+                f,
+                "could not pick an outgoing edge of node {:?}; its weights do not sum to a positive number",
+                node_id
+            ),
+            NodeAlreadyExistsError { node_id } => write!(f, "node {:?} already exists", node_id),
+            EdgeAlreadyExistsError { from, to } => {
+                write!(f, "edge {:?} -> {:?} already exists", from, to)
+            }
+            ActionAlreadyExistsError { node_id, action_id } => {
+                write!(f, "action {} already exists on node {:?}", action_id, node_id)
+            }
+            EdgeActionAlreadyExistsError {
+                from,
+                to,
+                action_id,
+            } => write!(
+                f,
+                "action {} already exists on edge {:?} -> {:?}",
+                action_id, from, to
+            ),
+            InvalidWeightError { from, to, weight } => write!(
+                f,
+                "edge {:?} -> {:?} has weight {}, which is not a finite, non-negative number",
+                from, to, weight
+            ),
+            ReducibleChainError { recurrent_classes } => write!(
+                f,
+                "the chain is reducible with {} recurrent classes: {:?}",
+                recurrent_classes.len(),
+                recurrent_classes
+            ),
+            PeriodicChainError { period } => write!(f, "the chain is periodic with period {}", period),
+            NotConvergedError { iterations } => {
+                write!(f, "did not converge after {} iterations", iterations)
+            }
+            SingularMatrixError => write!(f, "the linear system is singular"),
+            InvalidContextError { order, context } => write!(
+                f,
+                "context {:?} does not have {} node ids",
+                context, order
+            ),
+            UnknownContextError { context } => {
+                write!(f, "context {:?} has no transitions", context)
+            }
+            InvalidProbabilityError {
+                node_id,
+                probability,
+            } => write!(
+                f,
+                "node {:?} has probability {}, which is not a finite, non-negative number",
+                node_id, probability
+            ),
+            InvalidParameterError {
+                name,
+                value,
+                expected,
+            } => write!(f, "{} is {}, but must be {}", name, value, expected),
+            NotAbsorbingError { node_ids } => write!(
+                f,
+                "nodes {:?} cannot reach an absorbing node",
+                node_ids
+            ),
+            NameAlreadyExistsError { name } => write!(f, "a node named {:?} already exists", name),
+            NoFreeNodeIdError => write!(f, "every node id is already in use"),
+            UnknownNameError { name } => write!(f, "no node is named {:?}", name),
+            UnknownTokenError { token } => write!(f, "token {:?} is not in the vocabulary", token),
+            ActionDecodeError {
+                node_id,
+                action_id,
+                error,
+            } => write!(
+                f,
+                "could not decode action {} on node {:?}: {}",
+                action_id, node_id, error
+            ),
+            DeserializationError(error) => write!(f, "could not deserialize chain: {}", error),
+            SerializationError(error) => write!(f, "could not serialize chain: {}", error),
+            InvalidChainError(diagnostics) => {
+                write!(f, "chain failed validation")?;
+                for diagnostic in diagnostics.iter().filter(|d| d.is_error()) {
+                    write!(f, "\n  {}", diagnostic)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<I: fmt::Debug> Error for MarkovChainError<I> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MarkovChainError::ActionDecodeError { error, .. }
+            | MarkovChainError::DeserializationError(error)
+            | MarkovChainError::SerializationError(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl<I: PartialEq> PartialEq for MarkovChainError<I> {
+    fn eq(&self, other: &Self) -> bool {
+        use MarkovChainError::*;
+
+        match (self, other) {
+            (NoCurrentNodeError, NoCurrentNodeError)
+            | (SingularMatrixError, SingularMatrixError)
+            | (NoFreeNodeIdError, NoFreeNodeIdError) => true,
+            (NodeDoesNotExistError { node_id: a }, NodeDoesNotExistError { node_id: b })
+            | (NodeHasNoEdgesError { node_id: a }, NodeHasNoEdgesError { node_id: b })
+            | (TransitionFailedError { node_id: a }, TransitionFailedError { node_id: b })
+            | (NodeAlreadyExistsError { node_id: a }, NodeAlreadyExistsError { node_id: b }) => {
+                a == b
+            }
+            (
+                EdgeDoesNotExistError { from: a, to: b },
+                EdgeDoesNotExistError { from: c, to: d },
+            )
+            | (
+                EdgeAlreadyExistsError { from: a, to: b },
+                EdgeAlreadyExistsError { from: c, to: d },
+            ) => a == c && b == d,
+            (
+                ActionDoesNotExistError {
+                    node_id: a,
+                    action_id: b,
+                },
+                ActionDoesNotExistError {
+                    node_id: c,
+                    action_id: d,
+                },
+            )
+            | (
+                ActionAlreadyExistsError {
+                    node_id: a,
+                    action_id: b,
+                },
+                ActionAlreadyExistsError {
+                    node_id: c,
+                    action_id: d,
+                },
+            ) => a == c && b == d,
+            (
+                EdgeActionAlreadyExistsError {
+                    from,
+                    to,
+                    action_id,
+                },
+                EdgeActionAlreadyExistsError {
+                    from: other_from,
+                    to: other_to,
+                    action_id: other_action_id,
+                },
+            ) => from == other_from && to == other_to && action_id == other_action_id,
+            (
+                InvalidWeightError { from, to, weight },
+                InvalidWeightError {
+                    from: other_from,
+                    to: other_to,
+                    weight: other_weight,
+                },
+            ) => from == other_from && to == other_to && weight.to_bits() == other_weight.to_bits(),
+            (
+                ReducibleChainError {
+                    recurrent_classes: a,
+                },
+                ReducibleChainError {
+                    recurrent_classes: b,
+                },
+            ) => a == b,
+            (NotAbsorbingError { node_ids: a }, NotAbsorbingError { node_ids: b })
+            | (UnknownContextError { context: a }, UnknownContextError { context: b }) => a == b,
+            (
+                InvalidContextError { order, context },
+                InvalidContextError {
+                    order: other_order,
+                    context: other_context,
+                },
+            ) => order == other_order && context == other_context,
+            (PeriodicChainError { period: a }, PeriodicChainError { period: b })
+            | (NotConvergedError { iterations: a }, NotConvergedError { iterations: b }) => a == b,
+            (
+                InvalidProbabilityError {
+                    node_id,
+                    probability,
+                },
+                InvalidProbabilityError {
+                    node_id: other_node_id,
+                    probability: other_probability,
+                },
+            ) => node_id == other_node_id && probability.to_bits() == other_probability.to_bits(),
+            (
+                InvalidParameterError {
+                    name,
+                    value,
+                    expected,
+                },
+                InvalidParameterError {
+                    name: other_name,
+                    value: other_value,
+                    expected: other_expected,
+                },
+            ) => {
+                name == other_name
+                    && value.to_bits() == other_value.to_bits()
+                    && expected == other_expected
+            }
+            (UnknownTokenError { token: a }, UnknownTokenError { token: b })
+            | (NameAlreadyExistsError { name: a }, NameAlreadyExistsError { name: b })
+            | (UnknownNameError { name: a }, UnknownNameError { name: b }) => a == b,
+            // serde_json::Error has no PartialEq, so compare what it reports.
+            (
+                ActionDecodeError {
+                    node_id,
+                    action_id,
+                    error,
+                },
+                ActionDecodeError {
+                    node_id: other_node_id,
+                    action_id: other_action_id,
+                    error: other_error,
+                },
+            ) => {
+                node_id == other_node_id
+                    && action_id == other_action_id
+                    && error.to_string() == other_error.to_string()
+            }
+            (DeserializationError(a), DeserializationError(b))
+            | (SerializationError(a), SerializationError(b)) => a.to_string() == b.to_string(),
+            (InvalidChainError(a), InvalidChainError(b)) => a == b,
+            _ => false,
+        }
+    }
+}
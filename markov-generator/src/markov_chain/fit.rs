@@ -0,0 +1,149 @@
+use crate::markov_chain::{edge::Edge, node::Node, MarkovChain, MarkovChainError};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What the weights of a fitted chain's edges hold.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FitWeights {
+    /// Transition counts divided by the total count out of each node.
+    #[default]
+    Probabilities,
+    /// Raw (smoothed) transition counts.
+    Counts,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FitOptions {
+    /// Additive (Laplace) smoothing: a pseudo-count added to every pair of
+    /// observed nodes. Any value above zero connects every node to every
+    /// other, so the fitted chain has n² edges.
+    pub smoothing: f32,
+    pub weights: FitWeights,
+}
+
+impl MarkovChain {
+    /// Fits a chain to observed sequences of node ids, with one node per id
+    /// seen and edge weights set to transition probabilities.
+    pub fn fit<I, S>(sequences: I) -> MarkovChain
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<[u32]>,
+    {
+        MarkovChain::fit_with(sequences, FitOptions::default())
+            .expect("the default options are valid")
+    }
+
+    /// Like [`MarkovChain::fit`], with smoothing and the kind of edge weight
+    /// taken from `options`.
+    ///
+    /// Fails with `InvalidParameterError` if `options.smoothing` is negative
+    /// or not finite.
+    pub fn fit_with<I, S>(
+        sequences: I,
+        options: FitOptions,
+    ) -> Result<MarkovChain, MarkovChainError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<[u32]>,
+    {
+        if !(options.smoothing.is_finite() && options.smoothing >= 0.0) {
+            return Err(MarkovChainError::InvalidParameterError {
+                name: "smoothing",
+                value: options.smoothing as f64,
+                expected: "a finite, non-negative number",
+            });
+        }
+
+        let mut node_ids = BTreeSet::new();
+        let mut counts: BTreeMap<u32, BTreeMap<u32, f32>> = BTreeMap::new();
+        for sequence in sequences {
+            let sequence = sequence.as_ref();
+            node_ids.extend(sequence.iter().copied());
+            for pair in sequence.windows(2) {
+                *counts
+                    .entry(pair[0])
+                    .or_default()
+                    .entry(pair[1])
+                    .or_default() += 1.0;
+            }
+        }
+
+        if options.smoothing > 0.0 {
+            for &from in node_ids.iter() {
+                let row = counts.entry(from).or_default();
+                for &to in node_ids.iter() {
+                    *row.entry(to).or_default() += options.smoothing;
+                }
+            }
+        }
+
+        let mut mc = MarkovChain::new(None, None);
+        for &node_id in node_ids.iter() {
+            mc.add_node(Node::new(node_id, None));
+        }
+        for (from, row) in counts {
+            let total: f32 = match options.weights {
+                FitWeights::Probabilities => row.values().sum(),
+                FitWeights::Counts => 1.0,
+            };
+            for (to, count) in row {
+                mc.add_edge(Edge::new(from, to, count / total));
+            }
+        }
+
+        Ok(mc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit() {
+        let mc = MarkovChain::fit([vec![1, 2, 1, 3], vec![1, 2]]);
+
+        assert!(mc.node_exists(1) && mc.node_exists(2) && mc.node_exists(3));
+        assert_eq!(mc.get_edge(1, 2).unwrap().weight, 2.0 / 3.0);
+        assert_eq!(mc.get_edge(1, 3).unwrap().weight, 1.0 / 3.0);
+        assert_eq!(mc.get_edge(2, 1).unwrap().weight, 1.0);
+        assert!(mc.outgoing_edges(3).is_empty());
+    }
+
+    #[test]
+    fn test_fit_with_smoothing_and_counts() {
+        let options = FitOptions {
+            smoothing: 0.5,
+            weights: FitWeights::Counts,
+        };
+        let mc = MarkovChain::fit_with([[1, 2, 1]], options).unwrap();
+
+        assert_eq!(mc.get_edge(1, 2).unwrap().weight, 1.5);
+        assert_eq!(mc.get_edge(1, 1).unwrap().weight, 0.5);
+        assert_eq!(mc.get_edge(2, 1).unwrap().weight, 1.5);
+        assert_eq!(mc.get_edge(2, 2).unwrap().weight, 0.5);
+
+        let options = FitOptions {
+            smoothing: 1.0,
+            weights: FitWeights::Probabilities,
+        };
+        let mc = MarkovChain::fit_with([[1, 2]], options).unwrap();
+        assert_eq!(mc.get_edge(1, 2).unwrap().weight, 2.0 / 3.0);
+        assert_eq!(mc.get_edge(2, 1).unwrap().weight, 0.5);
+    }
+
+    #[test]
+    fn test_fit_with_invalid_smoothing() {
+        let options = FitOptions {
+            smoothing: -1.0,
+            weights: FitWeights::Counts,
+        };
+        assert_eq!(
+            MarkovChain::fit_with([[1, 2]], options).unwrap_err(),
+            MarkovChainError::InvalidParameterError {
+                name: "smoothing",
+                value: -1.0,
+                expected: "a finite, non-negative number",
+            }
+        );
+    }
+}
@@ -0,0 +1,384 @@
+use crate::markov_chain::{edge::Edge, node::Node, MarkovChain, MarkovChainError};
+use rand::Rng;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// An order-k Markov chain: the next node depends on the last `order` node
+/// ids rather than only the current one.
+///
+/// Each context's transitions are stored as [`Edge`]s whose `from` is the
+/// last id of the context. Serializes as
+/// `{ order, nodes, transitions: [{ context, to, weight }], current_context }`.
+#[derive(Deserialize, Debug)]
+#[serde(try_from = "HigherOrderData")]
+pub struct HigherOrderChain {
+    order: usize,
+    nodes: HashMap<u32, Node>,
+    transitions: HashMap<Vec<u32>, Vec<Edge>>,
+    current_context: Option<Vec<u32>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ContextEdge {
+    context: Vec<u32>,
+    to: u32,
+    weight: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct HigherOrderData {
+    order: usize,
+    nodes: Vec<Node>,
+    transitions: Vec<ContextEdge>,
+    current_context: Option<Vec<u32>>,
+}
+
+impl TryFrom<HigherOrderData> for HigherOrderChain {
+    type Error = MarkovChainError;
+
+    fn try_from(data: HigherOrderData) -> Result<Self, Self::Error> {
+        let mut chain = HigherOrderChain::new(data.order)?;
+        for node in data.nodes {
+            chain.add_node(node);
+        }
+        for transition in data.transitions {
+            chain.add_transition(&transition.context, transition.to, transition.weight)?;
+        }
+        if let Some(context) = data.current_context {
+            chain.check_context(&context)?;
+            chain.current_context = Some(context);
+        }
+        Ok(chain)
+    }
+}
+
+impl Serialize for HigherOrderChain {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut nodes: Vec<Node> = self.nodes.values().cloned().collect();
+        nodes.sort_by_key(|node| node.id);
+
+        let mut contexts: Vec<&Vec<u32>> = self.transitions.keys().collect();
+        contexts.sort();
+        let transitions = contexts
+            .into_iter()
+            .flat_map(|context| {
+                self.transitions[context].iter().map(|edge| ContextEdge {
+                    context: context.clone(),
+                    to: edge.to,
+                    weight: edge.weight,
+                })
+            })
+            .collect();
+
+        HigherOrderData {
+            order: self.order,
+            nodes,
+            transitions,
+            current_context: self.current_context.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// A [`HigherOrderChain`] expanded into an equivalent first-order chain by
+/// [`HigherOrderChain::to_first_order`].
+#[derive(Debug)]
+pub struct FirstOrderExpansion {
+    pub chain: MarkovChain,
+    /// The context each composite node stands for, indexed by node id.
+    pub contexts: Vec<Vec<u32>>,
+}
+
+impl HigherOrderChain {
+    /// Fails with `InvalidParameterError` if `order` is zero.
+    pub fn new(order: usize) -> Result<HigherOrderChain, MarkovChainError> {
+        if order == 0 {
+            return Err(MarkovChainError::InvalidParameterError {
+                name: "order",
+                value: 0.0,
+                expected: "at least 1",
+            });
+        }
+        Ok(HigherOrderChain {
+            order,
+            nodes: HashMap::new(),
+            transitions: HashMap::new(),
+            current_context: None,
+        })
+    }
+
+    /// Fits an order-`order` chain to observed sequences of node ids, with
+    /// edge weights set to transition probabilities per context.
+    ///
+    /// Fails with `InvalidParameterError` if `order` is zero.
+    pub fn fit<I, S>(order: usize, sequences: I) -> Result<HigherOrderChain, MarkovChainError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<[u32]>,
+    {
+        let mut chain = HigherOrderChain::new(order)?;
+        let mut counts: BTreeMap<Vec<u32>, BTreeMap<u32, f32>> = BTreeMap::new();
+        let mut node_ids = BTreeSet::new();
+
+        for sequence in sequences {
+            let sequence = sequence.as_ref();
+            node_ids.extend(sequence.iter().copied());
+            for window in sequence.windows(order + 1) {
+                *counts
+                    .entry(window[..order].to_vec())
+                    .or_default()
+                    .entry(window[order])
+                    .or_default() += 1.0;
+            }
+        }
+
+        for node_id in node_ids {
+            chain.add_node(Node::new(node_id, None));
+        }
+        for (context, row) in counts {
+            let total: f32 = row.values().sum();
+            let edges = row
+                .into_iter()
+                .map(|(to, count)| Edge::new(context[order - 1], to, count / total))
+                .collect();
+            chain.transitions.insert(context, edges);
+        }
+
+        Ok(chain)
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// Adds a node, replacing any existing node with the same id.
+    pub fn add_node(&mut self, node: Node) {
+        self.nodes.insert(node.id, node);
+    }
+
+    pub fn get_node(&self, node_id: u32) -> Option<&Node> {
+        self.nodes.get(&node_id)
+    }
+
+    /// Adds a transition from `context` to `to`. Fails if the context is not
+    /// `order` ids long.
+    pub fn add_transition(
+        &mut self,
+        context: &[u32],
+        to: u32,
+        weight: f32,
+    ) -> Result<(), MarkovChainError> {
+        self.check_context(context)?;
+        self.transitions
+            .entry(context.to_vec())
+            .or_default()
+            .push(Edge::new(context[self.order - 1], to, weight));
+        Ok(())
+    }
+
+    /// The transitions out of `context`, with `from` set to its last id.
+    pub fn get_transitions(&self, context: &[u32]) -> &[Edge] {
+        self.transitions.get(context).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn set_context(&mut self, context: &[u32]) -> Result<(), MarkovChainError> {
+        self.check_context(context)?;
+        self.current_context = Some(context.to_vec());
+        Ok(())
+    }
+
+    pub fn get_context(&self) -> Option<&[u32]> {
+        self.current_context.as_deref()
+    }
+
+    /// The last id of the current context.
+    pub fn get_current_node(&self) -> Option<u32> {
+        self.current_context
+            .as_ref()
+            .and_then(|c| c.last().copied())
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<(), MarkovChainError> {
+        self.next_with_rng(&mut rand::thread_rng())
+    }
+
+    /// Samples the next node from the current context's transitions and
+    /// shifts it into the context.
+    pub fn next_with_rng<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Result<(), MarkovChainError> {
+        let context = self
+            .current_context
+            .as_mut()
+            .ok_or(MarkovChainError::NoCurrentNodeError)?;
+
+        let edges = match self.transitions.get(context.as_slice()) {
+            Some(edges) if !edges.is_empty() => edges,
+            _ => {
+                return Err(MarkovChainError::UnknownContextError {
+                    context: context.clone(),
+                })
+            }
+        };
+        let index = MarkovChain::sample_cumulative(edges, rng).ok_or(
+            MarkovChainError::TransitionFailedError {
+                node_id: context[self.order - 1],
+            },
+        )?;
+
+        context.rotate_left(1);
+        context[self.order - 1] = edges[index].to;
+        Ok(())
+    }
+
+    /// Expands the chain into a first-order [`MarkovChain`] whose nodes are
+    /// the contexts. Every context that has transitions or can be reached
+    /// gets a node, numbered in ascending context order, carrying the actions
+    /// of the context's last node. The current context becomes the current
+    /// node.
+    pub fn to_first_order(&self) -> FirstOrderExpansion {
+        let mut contexts: BTreeSet<Vec<u32>> = BTreeSet::new();
+        for (context, edges) in self.transitions.iter() {
+            contexts.insert(context.clone());
+            for edge in edges {
+                contexts.insert(self.shift(context, edge.to));
+            }
+        }
+        contexts.extend(self.current_context.clone());
+
+        let contexts: Vec<Vec<u32>> = contexts.into_iter().collect();
+        let ids: HashMap<&[u32], u32> = contexts
+            .iter()
+            .enumerate()
+            .map(|(id, context)| (context.as_slice(), id as u32))
+            .collect();
+
+        let mut chain = MarkovChain::new(None, None);
+        for (context, &id) in ids.iter() {
+            let actions = self
+                .get_node(context[self.order - 1])
+                .map(|node| node.actions.clone());
+            chain.add_node(Node::new(id, actions));
+        }
+        for context in contexts.iter() {
+            for edge in self.get_transitions(context) {
+                let to = ids[self.shift(context, edge.to).as_slice()];
+                chain.add_edge(Edge::new(ids[context.as_slice()], to, edge.weight));
+            }
+        }
+        if let Some(context) = self.current_context.as_ref() {
+            chain.set_current_node(ids[context.as_slice()]).unwrap();
+        }
+
+        FirstOrderExpansion { chain, contexts }
+    }
+
+    fn shift(&self, context: &[u32], to: u32) -> Vec<u32> {
+        let mut next = context[1..].to_vec();
+        next.push(to);
+        next
+    }
+
+    fn check_context(&self, context: &[u32]) -> Result<(), MarkovChainError> {
+        if context.len() != self.order {
+            return Err(MarkovChainError::InvalidContextError {
+                order: self.order,
+                context: context.to_vec(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markov_chain::action::Action;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_fit_and_walk() {
+        // After (1, 2) comes 3, but after (3, 2) comes 1.
+        let mut chain = HigherOrderChain::fit(2, [[1, 2, 3, 2, 1, 2, 3]]).unwrap();
+        assert_eq!(chain.get_transitions(&[1, 2]), &[Edge::new(2, 3, 1.0)]);
+        assert_eq!(chain.get_transitions(&[3, 2]), &[Edge::new(2, 1, 1.0)]);
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        chain.set_context(&[1, 2]).unwrap();
+        chain.next_with_rng(&mut rng).unwrap();
+        chain.next_with_rng(&mut rng).unwrap();
+        chain.next_with_rng(&mut rng).unwrap();
+        assert_eq!(chain.get_context(), Some(&[2, 1][..]));
+
+        assert_eq!(
+            chain.set_context(&[1]),
+            Err(MarkovChainError::InvalidContextError {
+                order: 2,
+                context: vec![1]
+            })
+        );
+        chain.set_context(&[3, 1]).unwrap();
+        assert_eq!(
+            chain.next(),
+            Err(MarkovChainError::UnknownContextError {
+                context: vec![3, 1]
+            })
+        );
+    }
+
+    #[test]
+    fn test_serialization_round_trip() {
+        let mut chain = HigherOrderChain::fit(2, [[1, 2, 3, 2, 1]]).unwrap();
+        chain.set_context(&[2, 3]).unwrap();
+
+        let serialized = serde_json::to_string(&chain).unwrap();
+        let deserialized: HigherOrderChain = serde_json::from_str(&serialized).unwrap();
+
+        assert_eq!(serde_json::to_string(&deserialized).unwrap(), serialized);
+        assert!(serde_json::from_str::<HigherOrderChain>(
+            r#"{"order":2,"nodes":[],"transitions":[{"context":[1],"to":2,"weight":1.0}],"current_context":null}"#
+        )
+        .is_err());
+
+        let error = serde_json::from_str::<HigherOrderChain>(
+            r#"{"order":0,"nodes":[],"transitions":[],"current_context":null}"#,
+        )
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("order is 0, but must be at least 1"));
+    }
+
+    #[test]
+    fn test_zero_order() {
+        let expected = MarkovChainError::InvalidParameterError {
+            name: "order",
+            value: 0.0,
+            expected: "at least 1",
+        };
+        assert_eq!(HigherOrderChain::new(0).unwrap_err(), expected);
+        assert_eq!(HigherOrderChain::fit(0, [[1, 2]]).unwrap_err(), expected);
+    }
+
+    #[test]
+    fn test_to_first_order() {
+        let mut chain = HigherOrderChain::fit(2, [[1, 2, 3, 2, 1]]).unwrap();
+        chain.add_node(Node::new(2, Some(vec![Action::new(7, None)])));
+        chain.set_context(&[1, 2]).unwrap();
+
+        let expansion = chain.to_first_order();
+        assert_eq!(
+            expansion.contexts,
+            vec![vec![1, 2], vec![2, 1], vec![2, 3], vec![3, 2]]
+        );
+
+        let mc = &expansion.chain;
+        assert_eq!(mc.get_current_node(), Some(0));
+        assert_eq!(mc.get_edge(0, 2).unwrap().weight, 1.0);
+        assert_eq!(mc.get_edge(2, 3).unwrap().weight, 1.0);
+        assert_eq!(mc.get_edge(3, 1).unwrap().weight, 1.0);
+        assert!(mc.outgoing_edges(1).is_empty());
+        assert_eq!(mc.get_node_actions(0), Some(&vec![Action::new(7, None)]));
+        assert_eq!(mc.get_node_actions(2), Some(&vec![]));
+    }
+}
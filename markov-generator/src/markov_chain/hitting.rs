@@ -0,0 +1,194 @@
+use crate::markov_chain::{
+    linalg::solve, transition_matrix::TransitionMatrix, MarkovChain, MarkovChainError,
+};
+use std::collections::HashMap;
+
+impl MarkovChain {
+    /// The expected number of steps for a walk from each node to first reach
+    /// any node in `targets`, keyed by node id. Targets have a hitting time of
+    /// zero, and nodes from which the walk may never reach a target get
+    /// `f64::INFINITY`.
+    ///
+    /// Terminal nodes and nodes without outgoing edges end the walk, so they
+    /// only count as reaching the targets if they are targets themselves.
+    pub fn expected_hitting_times(
+        &self,
+        targets: &[u32],
+    ) -> Result<HashMap<u32, f64>, MarkovChainError> {
+        let (matrix, is_target) = self.first_passage_matrix(targets)?;
+        let n = matrix.len();
+
+        // Nodes that cannot reach a target, and then nodes that can reach one
+        // of those with positive probability, never hit the targets for sure.
+        let mut predecessors = vec![Vec::new(); n];
+        for i in (0..n).filter(|&i| !is_target[i]) {
+            for &(j, _) in matrix.row(i) {
+                predecessors[j].push(i);
+            }
+        }
+        let reaches_target = backwards_closure(&predecessors, &is_target);
+        let misses_target: Vec<bool> = reaches_target.iter().map(|&reaches| !reaches).collect();
+        let infinite = backwards_closure(&predecessors, &misses_target);
+
+        let unknown: Vec<usize> = (0..n).filter(|&i| !is_target[i] && !infinite[i]).collect();
+        let mut position = vec![None; n];
+        for (k, &i) in unknown.iter().enumerate() {
+            position[i] = Some(k);
+        }
+
+        // h = 1 + Q h over the nodes whose hitting time is finite.
+        let m = unknown.len();
+        let mut a = vec![vec![0.0; m]; m];
+        for (k, &i) in unknown.iter().enumerate() {
+            a[k][k] += 1.0;
+            for &(j, p) in matrix.row(i) {
+                if let Some(l) = position[j] {
+                    a[k][l] -= p;
+                }
+            }
+        }
+        let h = solve(a, vec![1.0; m]).ok_or(MarkovChainError::SingularMatrixError)?;
+
+        let mut times = vec![0.0; n];
+        for i in 0..n {
+            if infinite[i] {
+                times[i] = f64::INFINITY;
+            }
+        }
+        for (k, &i) in unknown.iter().enumerate() {
+            times[i] = h[k];
+        }
+
+        Ok(matrix.to_map(&times))
+    }
+
+    /// The distribution of the first time a walk from `from` reaches any node
+    /// in `targets`: element `t` is the probability that this happens after
+    /// exactly `t` steps, for `t` in `0..=horizon`. The remaining mass is the
+    /// probability of not having arrived by `horizon`.
+    pub fn first_passage_distribution(
+        &self,
+        from: u32,
+        targets: &[u32],
+        horizon: usize,
+    ) -> Result<Vec<f64>, MarkovChainError> {
+        let (matrix, is_target) = self.first_passage_matrix(targets)?;
+        let start = matrix
+            .index_of(from)
+            .ok_or(MarkovChainError::NodeDoesNotExistError { node_id: from })?;
+
+        let mut distribution = vec![0.0; matrix.len()];
+        distribution[start] = 1.0;
+        let mut passage = Vec::with_capacity(horizon + 1);
+
+        for t in 0..=horizon {
+            if t > 0 {
+                distribution = matrix.step(&distribution);
+            }
+            let arrived: f64 = (0..matrix.len())
+                .filter(|&i| is_target[i])
+                .map(|i| std::mem::take(&mut distribution[i]))
+                .sum();
+            passage.push(arrived);
+        }
+
+        Ok(passage)
+    }
+
+    /// The probability that a walk from `from` reaches any node in `targets`
+    /// within `k` steps.
+    pub fn hitting_probability_within(
+        &self,
+        from: u32,
+        targets: &[u32],
+        k: usize,
+    ) -> Result<f64, MarkovChainError> {
+        Ok(self
+            .first_passage_distribution(from, targets, k)?
+            .into_iter()
+            .sum())
+    }
+
+    /// The transition matrix with terminal nodes made absorbing, and which of
+    /// its rows are targets.
+    fn first_passage_matrix(
+        &self,
+        targets: &[u32],
+    ) -> Result<(TransitionMatrix, Vec<bool>), MarkovChainError> {
+        let mut matrix = self.transition_matrix()?;
+        matrix.absorb_terminal_nodes(self);
+
+        let mut is_target = vec![false; matrix.len()];
+        for &node_id in targets {
+            let i = matrix
+                .index_of(node_id)
+                .ok_or(MarkovChainError::NodeDoesNotExistError { node_id })?;
+            is_target[i] = true;
+        }
+
+        Ok((matrix, is_target))
+    }
+}
+
+/// Marks every row that can reach a row in `seeds` by following
+/// `predecessors` backwards.
+pub(crate) fn backwards_closure(predecessors: &[Vec<usize>], seeds: &[bool]) -> Vec<bool> {
+    let mut marked = seeds.to_vec();
+    let mut stack: Vec<usize> = (0..seeds.len()).filter(|&i| seeds[i]).collect();
+    while let Some(j) = stack.pop() {
+        for &i in predecessors[j].iter() {
+            if !marked[i] {
+                marked[i] = true;
+                stack.push(i);
+            }
+        }
+    }
+    marked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markov_chain::test_util::chain;
+
+    #[test]
+    fn test_expected_hitting_times() {
+        // From 1, each step reaches 2 with probability 1/4.
+        let mc = chain(
+            &[1, 2, 3, 4],
+            &[
+                (1, 1, 3.0),
+                (1, 2, 1.0),
+                (2, 1, 1.0),
+                (3, 4, 1.0),
+                (3, 1, 1.0),
+            ],
+        );
+        let times = mc.expected_hitting_times(&[2]).unwrap();
+
+        assert!((times[&1] - 4.0).abs() < 1e-9);
+        assert_eq!(times[&2], 0.0);
+        // 3 falls into the sink 4 half of the time.
+        assert_eq!(times[&3], f64::INFINITY);
+        assert_eq!(times[&4], f64::INFINITY);
+    }
+
+    #[test]
+    fn test_first_passage_distribution() {
+        let mc = chain(&[1, 2], &[(1, 1, 3.0), (1, 2, 1.0), (2, 1, 1.0)]);
+
+        let passage = mc.first_passage_distribution(1, &[2], 3).unwrap();
+        let expected = [0.0, 0.25, 0.1875, 0.140625];
+        for (actual, expected) in passage.iter().zip(expected) {
+            assert!((actual - expected).abs() < 1e-12);
+        }
+
+        let within = mc.hitting_probability_within(1, &[2], 3).unwrap();
+        assert!((within - (1.0 - 0.75f64.powi(3))).abs() < 1e-12);
+        assert_eq!(mc.hitting_probability_within(2, &[2], 0), Ok(1.0));
+        assert_eq!(
+            mc.hitting_probability_within(1, &[9], 3),
+            Err(MarkovChainError::NodeDoesNotExistError { node_id: 9 })
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! Dense linear algebra for the analysis modules.
+
+const PIVOT_EPSILON: f64 = 1e-12;
+
+/// Solves `a * x = b` for every column of `b` by Gaussian elimination with
+/// partial pivoting. `b` is given row by row. Returns `None` if `a` is
+/// singular.
+pub(crate) fn solve_columns(mut a: Vec<Vec<f64>>, mut b: Vec<Vec<f64>>) -> Option<Vec<Vec<f64>>> {
+    let n = a.len();
+
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < PIVOT_EPSILON {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        let (pivot_a, pivot_b) = (a[col].clone(), b[col].clone());
+        for row in col + 1..n {
+            let factor = a[row][col] / pivot_a[col];
+            if factor == 0.0 {
+                continue;
+            }
+            for (x, p) in a[row][col..].iter_mut().zip(&pivot_a[col..]) {
+                *x -= factor * p;
+            }
+            for (x, p) in b[row].iter_mut().zip(&pivot_b) {
+                *x -= factor * p;
+            }
+        }
+    }
+
+    for col in (0..n).rev() {
+        for k in 0..b[col].len() {
+            let sum: f64 = (col + 1..n).map(|j| a[col][j] * b[j][k]).sum();
+            b[col][k] = (b[col][k] - sum) / a[col][col];
+        }
+    }
+
+    Some(b)
+}
+
+/// Solves `a * x = b` for a single right-hand side.
+pub(crate) fn solve(a: Vec<Vec<f64>>, b: Vec<f64>) -> Option<Vec<f64>> {
+    let b = b.into_iter().map(|value| vec![value]).collect();
+    solve_columns(a, b).map(|x| x.into_iter().map(|row| row[0]).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solve() {
+        let a = vec![vec![0.0, 2.0], vec![1.0, 1.0]];
+        let x = solve(a, vec![4.0, 3.0]).unwrap();
+        assert!((x[0] - 1.0).abs() < 1e-12);
+        assert!((x[1] - 2.0).abs() < 1e-12);
+
+        assert_eq!(
+            solve(vec![vec![1.0, 1.0], vec![2.0, 2.0]], vec![1.0, 2.0]),
+            None
+        );
+    }
+}
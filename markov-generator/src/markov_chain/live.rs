@@ -0,0 +1,636 @@
+use crate::markov_chain::{
+    action::Action,
+    sink::{ActionDispatcher, ActionSink, SinkFailure},
+    MarkovChain, MarkovChainError,
+};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A source of time for a [`LiveRunner`].
+pub trait Clock {
+    /// The time elapsed since the clock's origin.
+    fn now(&self) -> Duration;
+    fn sleep(&mut self, duration: Duration);
+}
+
+/// Wall-clock time, measured from when the clock was created.
+#[derive(Clone, Debug)]
+pub struct SystemClock {
+    origin: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// A clock that only moves when slept on or advanced, for deterministic tests.
+#[derive(Clone, Debug, Default)]
+pub struct SimulatedClock {
+    now: Duration,
+}
+
+impl SimulatedClock {
+    pub fn new() -> SimulatedClock {
+        SimulatedClock::default()
+    }
+
+    pub fn advance(&mut self, duration: Duration) {
+        self.now += duration;
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> Duration {
+        self.now
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+/// How long a [`LiveRunner`] stays in a node before stepping on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Dwell {
+    Fixed(Duration),
+    /// Uniformly distributed between `min` and `max`, inclusive.
+    Uniform {
+        min: Duration,
+        max: Duration,
+    },
+    /// Exponentially distributed with the given mean.
+    Exponential {
+        mean: Duration,
+    },
+    /// A number of seconds read from the value of action `action_id`, at the
+    /// JSON pointer `pointer` (`""` for the whole value).
+    FromAction {
+        action_id: u32,
+        pointer: String,
+    },
+}
+
+impl Default for Dwell {
+    fn default() -> Self {
+        Dwell::Fixed(Duration::ZERO)
+    }
+}
+
+impl Dwell {
+    /// Draws a dwell time. [`Dwell::FromAction`] looks the action up in
+    /// `actions` and gives `None` if it is missing or does not hold a
+    /// non-negative number.
+    fn sample<R: Rng + ?Sized>(&self, actions: &[Action], rng: &mut R) -> Option<Duration> {
+        match self {
+            Dwell::Fixed(duration) => Some(*duration),
+            Dwell::Uniform { min, max } if min >= max => Some(*min),
+            Dwell::Uniform { min, max } => Some(rng.gen_range(*min..=*max)),
+            Dwell::Exponential { mean } => {
+                let u: f64 = rng.gen();
+                Duration::try_from_secs_f64(-mean.as_secs_f64() * (1.0 - u).ln()).ok()
+            }
+            Dwell::FromAction { action_id, pointer } => actions
+                .iter()
+                .find(|action| action.id == *action_id)
+                .and_then(|action| action.value.pointer(pointer))
+                .and_then(|value| value.as_f64())
+                .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok()),
+        }
+    }
+}
+
+/// Whether a [`LiveRunner`] is advancing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RunState {
+    #[default]
+    Running,
+    /// The time left in the current node is frozen until resumed.
+    Paused,
+    /// The runner will not step again.
+    Stopped,
+}
+
+/// A handle for controlling a [`LiveRunner`], possibly from another thread or
+/// from one of its own callbacks. A running runner notices changes within
+/// its poll interval.
+#[derive(Clone, Debug, Default)]
+pub struct LiveHandle {
+    state: Arc<Mutex<RunState>>,
+}
+
+impl LiveHandle {
+    pub fn state(&self) -> RunState {
+        *self.state.lock().unwrap()
+    }
+
+    /// Pauses a running runner.
+    pub fn pause(&self) {
+        self.transition(RunState::Running, RunState::Paused);
+    }
+
+    /// Resumes a paused runner.
+    pub fn resume(&self) {
+        self.transition(RunState::Paused, RunState::Running);
+    }
+
+    pub fn stop(&self) {
+        *self.state.lock().unwrap() = RunState::Stopped;
+    }
+
+    fn transition(&self, from: RunState, to: RunState) {
+        let mut state = self.state.lock().unwrap();
+        if *state == from {
+            *state = to;
+        }
+    }
+}
+
+type ActionCallback = Box<dyn FnMut(u32, &Action)>;
+
+/// Walks a chain in real time, staying in each node for its dwell time and
+/// emitting the actions that fire to callbacks and sinks.
+///
+/// Actions fire as in [`MarkovChain::step_and_act`]. The time spent in the
+/// node entered is taken from the dwell set for the edge that was taken,
+/// else the one set for the node, else the default. A [`Dwell::FromAction`]
+/// that cannot be read falls through to the next setting, and to zero after
+/// the default. Steps are scheduled from when the previous one was due rather
+/// than when it ran, so delays do not accumulate. Zero dwells are allowed but
+/// take at most one step per poll, so a blocking run takes them one poll
+/// interval apart.
+///
+/// The runner starts dwelling in the chain's current node on its first poll,
+/// without firing that node's actions, and stops after entering a terminal
+/// node or a node without outgoing edges.
+pub struct LiveRunner<C = SystemClock> {
+    chain: MarkovChain,
+    clock: C,
+    rng: ChaCha8Rng,
+    handle: LiveHandle,
+    poll_interval: Duration,
+    default_dwell: Dwell,
+    node_dwell: HashMap<u32, Dwell>,
+    edge_dwell: HashMap<(u32, u32), Dwell>,
+    dispatcher: ActionDispatcher,
+    callbacks: Vec<ActionCallback>,
+    failures: Vec<SinkFailure>,
+    /// When the next step is due, while running.
+    deadline: Option<Duration>,
+    /// The time left in the current node, while paused.
+    remaining: Option<Duration>,
+}
+
+impl LiveRunner<SystemClock> {
+    pub fn new(chain: MarkovChain) -> LiveRunner<SystemClock> {
+        LiveRunner::with_clock(chain, SystemClock::new())
+    }
+}
+
+impl<C: Clock> LiveRunner<C> {
+    pub fn with_clock(chain: MarkovChain, clock: C) -> LiveRunner<C> {
+        LiveRunner {
+            chain,
+            clock,
+            rng: ChaCha8Rng::from_entropy(),
+            handle: LiveHandle::default(),
+            poll_interval: Duration::from_millis(10),
+            default_dwell: Dwell::default(),
+            node_dwell: HashMap::new(),
+            edge_dwell: HashMap::new(),
+            dispatcher: ActionDispatcher::new(),
+            callbacks: Vec::new(),
+            failures: Vec::new(),
+            deadline: None,
+            remaining: None,
+        }
+    }
+
+    pub fn chain(&self) -> &MarkovChain {
+        &self.chain
+    }
+
+    pub fn into_chain(self) -> MarkovChain {
+        self.chain
+    }
+
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    pub fn clock_mut(&mut self) -> &mut C {
+        &mut self.clock
+    }
+
+    /// Seeds the RNG that dwell times are drawn from. The chain's own RNG
+    /// still picks the transitions.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+    }
+
+    /// Sets the longest a blocking run sleeps before checking for pause and
+    /// stop requests.
+    pub fn set_poll_interval(&mut self, poll_interval: Duration) {
+        self.poll_interval = poll_interval;
+    }
+
+    pub fn set_default_dwell(&mut self, dwell: Dwell) {
+        self.default_dwell = dwell;
+    }
+
+    pub fn set_node_dwell(&mut self, node_id: u32, dwell: Dwell) {
+        self.node_dwell.insert(node_id, dwell);
+    }
+
+    /// Sets the dwell time in `to_node_id` after arriving from `from_node_id`.
+    pub fn set_edge_dwell(&mut self, from_node_id: u32, to_node_id: u32, dwell: Dwell) {
+        self.edge_dwell.insert((from_node_id, to_node_id), dwell);
+    }
+
+    pub fn add_sink(&mut self, sink: impl ActionSink + 'static) {
+        self.dispatcher.add_sink(sink);
+    }
+
+    /// Calls `callback` with every action that fires and the node it fired for.
+    pub fn on_action(&mut self, callback: impl FnMut(u32, &Action) + 'static) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    /// Takes the sink failures collected since the last call.
+    pub fn take_failures(&mut self) -> Vec<SinkFailure> {
+        std::mem::take(&mut self.failures)
+    }
+
+    pub fn handle(&self) -> LiveHandle {
+        self.handle.clone()
+    }
+
+    pub fn state(&self) -> RunState {
+        self.handle.state()
+    }
+
+    pub fn pause(&mut self) {
+        self.handle.pause();
+        self.sync_state();
+    }
+
+    pub fn resume(&mut self) {
+        self.handle.resume();
+        self.sync_state();
+    }
+
+    pub fn stop(&mut self) {
+        self.handle.stop();
+        self.sync_state();
+    }
+
+    /// The time left before the next step, if the runner has started and
+    /// not stopped.
+    pub fn time_to_next_step(&self) -> Option<Duration> {
+        self.remaining.or_else(|| {
+            self.deadline
+                .map(|deadline| deadline.saturating_sub(self.clock.now()))
+        })
+    }
+
+    /// Takes every step that is due at the clock's current time without
+    /// sleeping, and returns how many were taken. A step followed by a zero
+    /// dwell ends the poll, so the next one is left for the following poll.
+    pub fn poll(&mut self) -> Result<usize, MarkovChainError> {
+        self.sync_state();
+        if self.state() != RunState::Running {
+            return Ok(0);
+        }
+
+        let now = self.clock.now();
+        if self.deadline.is_none() {
+            self.deadline = Some(self.start(now)?);
+        }
+
+        let mut steps = 0;
+        while let Some(deadline) = self.deadline.filter(|&deadline| deadline <= now) {
+            self.deadline = self.advance(deadline)?;
+            steps += 1;
+            // Otherwise a cycle of zero dwells would never return.
+            if self.deadline == Some(deadline) {
+                break;
+            }
+        }
+        Ok(steps)
+    }
+
+    /// Runs until the clock reaches `end` or the runner stops, sleeping on
+    /// the clock between steps.
+    pub fn run_until(&mut self, end: Duration) -> Result<(), MarkovChainError> {
+        loop {
+            self.poll()?;
+            let now = self.clock.now();
+            if self.state() == RunState::Stopped || now >= end {
+                return Ok(());
+            }
+
+            // A step already due after a zero dwell waits for the next poll.
+            let wake = self
+                .deadline
+                .filter(|&deadline| deadline > now)
+                .map_or(end, |deadline| deadline.min(end));
+            let wake = wake.min(now.saturating_add(self.poll_interval));
+            self.clock.sleep(wake.saturating_sub(now));
+        }
+    }
+
+    /// Runs for `duration` of clock time, or until the runner stops.
+    pub fn run_for(&mut self, duration: Duration) -> Result<(), MarkovChainError> {
+        self.run_until(self.clock.now().saturating_add(duration))
+    }
+
+    /// Runs until the runner stops.
+    pub fn run(&mut self) -> Result<(), MarkovChainError> {
+        self.run_until(Duration::MAX)
+    }
+
+    /// Brings the schedule in line with the handle's state.
+    fn sync_state(&mut self) {
+        let now = self.clock.now();
+        match self.handle.state() {
+            RunState::Running => {
+                if let Some(remaining) = self.remaining.take() {
+                    self.deadline = Some(now.saturating_add(remaining));
+                }
+            }
+            RunState::Paused => {
+                if let Some(deadline) = self.deadline.take() {
+                    self.remaining = Some(deadline.saturating_sub(now));
+                }
+            }
+            RunState::Stopped => {
+                self.deadline = None;
+                self.remaining = None;
+            }
+        }
+    }
+
+    /// Starts dwelling in the current node and returns when the first step
+    /// is due.
+    fn start(&mut self, now: Duration) -> Result<Duration, MarkovChainError> {
+        let node_id = self
+            .chain
+            .get_current_node()
+            .ok_or(MarkovChainError::NoCurrentNodeError)?;
+        Ok(now.saturating_add(self.dwell(None, node_id)))
+    }
+
+    /// Takes the step that was due at `due` and returns when the next one is
+    /// due, or `None` once the runner has stopped.
+    fn advance(&mut self, due: Duration) -> Result<Option<Duration>, MarkovChainError> {
+        let from = self.chain.get_current_node();
+        let events = match self.chain.fire() {
+            Ok(events) => events,
+            Err(MarkovChainError::NodeHasNoEdgesError { .. }) => {
+                self.handle.stop();
+                return Ok(None);
+            }
+            Err(error) => {
+                self.handle.stop();
+                return Err(error);
+            }
+        };
+
+        for (node_id, action) in &events {
+            self.failures
+                .extend(self.dispatcher.dispatch(node_id, action));
+            for callback in self.callbacks.iter_mut() {
+                callback(*node_id, action);
+            }
+        }
+
+        // A callback may have paused or stopped the runner.
+        let to = self.chain.get_current_node().unwrap();
+        if self.chain.is_terminal_node(to) || self.state() == RunState::Stopped {
+            self.handle.stop();
+            return Ok(None);
+        }
+
+        let next = due.saturating_add(self.dwell(from, to));
+        if self.state() == RunState::Paused {
+            self.remaining = Some(next.saturating_sub(self.clock.now()));
+            return Ok(None);
+        }
+        Ok(Some(next))
+    }
+
+    /// The dwell time in `to` after arriving from `from`.
+    fn dwell(&mut self, from: Option<u32>, to: u32) -> Duration {
+        let node_actions = self
+            .chain
+            .get_node_actions(to)
+            .map_or(&[][..], Vec::as_slice);
+        let edge = from.and_then(|from| Some((self.edge_dwell.get(&(from, to))?, from)));
+
+        edge.and_then(|(dwell, from)| {
+            let edge_actions = self
+                .chain
+                .get_edge_actions(from, to)
+                .map_or(&[][..], Vec::as_slice);
+            dwell.sample(edge_actions, &mut self.rng)
+        })
+        .or_else(|| {
+            self.node_dwell
+                .get(&to)
+                .and_then(|dwell| dwell.sample(node_actions, &mut self.rng))
+        })
+        .or_else(|| self.default_dwell.sample(node_actions, &mut self.rng))
+        .unwrap_or(Duration::ZERO)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markov_chain::{edge::Edge, node::Node};
+    use serde_json::json;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn secs(seconds: f64) -> Duration {
+        Duration::from_secs_f64(seconds)
+    }
+
+    /// 1 -> 2 -> 3, where 3 is terminal and every node has an action with
+    /// its own id.
+    fn create_line_runner() -> LiveRunner<SimulatedClock> {
+        let nodes = (1..=3)
+            .map(|id| Node::new(id, Some(vec![Action::new(id, None)])))
+            .collect();
+        let edges = vec![Edge::new(1, 2, 1.0), Edge::new(2, 3, 1.0)];
+        let mut mc = MarkovChain::new(Some(nodes), Some(edges));
+        mc.add_terminal_node(3);
+        mc.set_current_node(1).unwrap();
+        LiveRunner::with_clock(mc, SimulatedClock::new())
+    }
+
+    #[test]
+    fn test_runner_follows_dwell_times() {
+        let mut runner = create_line_runner();
+        runner.set_node_dwell(1, Dwell::Fixed(secs(2.0)));
+        runner.set_node_dwell(2, Dwell::Fixed(secs(1.0)));
+        runner.set_edge_dwell(1, 2, Dwell::Fixed(secs(5.0)));
+
+        let fired = Rc::new(RefCell::new(Vec::new()));
+        let log = fired.clone();
+        runner.on_action(move |node_id, action| log.borrow_mut().push((node_id, action.id)));
+
+        runner.run_for(secs(1.5)).unwrap();
+        assert_eq!(runner.chain().get_current_node(), Some(1));
+        assert_eq!(runner.time_to_next_step(), Some(secs(0.5)));
+
+        runner.run_for(secs(0.5)).unwrap();
+        assert_eq!(runner.chain().get_current_node(), Some(2));
+        assert_eq!(*fired.borrow(), vec![(2, 2)]);
+        // The edge dwell overrides node 2's own.
+        assert_eq!(runner.time_to_next_step(), Some(secs(5.0)));
+
+        runner.run().unwrap();
+        assert_eq!(runner.clock().now(), secs(7.0));
+        assert_eq!(runner.state(), RunState::Stopped);
+        assert_eq!(*fired.borrow(), vec![(2, 2), (3, 3)]);
+    }
+
+    #[test]
+    fn test_pause_resume_and_stop() {
+        let mut runner = create_line_runner();
+        runner.set_default_dwell(Dwell::Fixed(secs(2.0)));
+
+        runner.run_for(secs(1.5)).unwrap();
+        runner.pause();
+        runner.run_for(secs(60.0)).unwrap();
+        assert_eq!(runner.chain().get_current_node(), Some(1));
+        assert_eq!(runner.time_to_next_step(), Some(secs(0.5)));
+
+        // A stop requested from a callback ends the run after that step.
+        let handle = runner.handle();
+        runner.on_action(move |_, _| handle.stop());
+        runner.resume();
+        runner.run().unwrap();
+        assert_eq!(runner.clock().now(), secs(62.0));
+        assert_eq!(runner.state(), RunState::Stopped);
+        assert_eq!(runner.chain().get_current_node(), Some(2));
+        assert_eq!(runner.time_to_next_step(), None);
+
+        runner.resume();
+        assert_eq!(runner.state(), RunState::Stopped);
+    }
+
+    #[test]
+    fn test_random_and_action_dwell_times() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let uniform = Dwell::Uniform {
+            min: secs(1.0),
+            max: secs(2.0),
+        };
+        let exponential = Dwell::Exponential { mean: secs(1.0) };
+        let mut total = Duration::ZERO;
+        for _ in 0..1000 {
+            let dwell = uniform.sample(&[], &mut rng).unwrap();
+            assert!(dwell >= secs(1.0) && dwell <= secs(2.0));
+            total += exponential.sample(&[], &mut rng).unwrap();
+        }
+        assert!((total.as_secs_f64() / 1000.0 - 1.0).abs() < 0.1);
+
+        let actions = [Action::new(9, Some(json!({ "dwell": 1.5 })))];
+        let from_action = |pointer: &str| Dwell::FromAction {
+            action_id: 9,
+            pointer: pointer.to_string(),
+        };
+        assert_eq!(
+            from_action("/dwell").sample(&actions, &mut rng),
+            Some(secs(1.5))
+        );
+        assert_eq!(from_action("/missing").sample(&actions, &mut rng), None);
+
+        // An unreadable action dwell falls back to the default.
+        let mut runner = create_line_runner();
+        runner.set_default_dwell(Dwell::Fixed(secs(4.0)));
+        runner.set_node_dwell(1, from_action("/dwell"));
+        runner
+            .chain
+            .add_node_actions(1, &[Action::new(9, Some(json!({ "dwell": 0.25 })))]);
+        runner.set_node_dwell(2, from_action("/dwell"));
+
+        runner.poll().unwrap();
+        assert_eq!(runner.time_to_next_step(), Some(secs(0.25)));
+        runner.run_for(secs(0.25)).unwrap();
+        assert_eq!(runner.time_to_next_step(), Some(secs(4.0)));
+    }
+
+    #[test]
+    fn test_zero_dwell_cycle_yields_between_steps() {
+        let nodes = (1..=3).map(|id| Node::new(id, None)).collect();
+        let edges = vec![
+            Edge::new(1, 2, 1.0),
+            Edge::new(2, 3, 1.0),
+            Edge::new(3, 1, 1.0),
+        ];
+        let mut mc = MarkovChain::new(Some(nodes), Some(edges));
+        mc.set_current_node(1).unwrap();
+        let mut runner = LiveRunner::with_clock(mc, SimulatedClock::new());
+
+        assert_eq!(runner.poll(), Ok(1));
+        assert_eq!(runner.poll(), Ok(1));
+        assert_eq!(runner.chain().get_current_node(), Some(3));
+
+        // One step at each of the 11 polls from 0s to 1s.
+        runner.set_poll_interval(secs(0.1));
+        runner.run_for(secs(1.0)).unwrap();
+        assert_eq!(runner.clock().now(), secs(1.0));
+        assert_eq!(runner.state(), RunState::Running);
+        assert_eq!(runner.chain().get_current_node(), Some(2));
+    }
+
+    #[test]
+    fn test_runner_reports_errors_and_sink_failures() {
+        struct Failing;
+
+        impl ActionSink for Failing {
+            fn send(&mut self, _: &u32, _: &Action) -> std::io::Result<()> {
+                Err(std::io::Error::other("unavailable"))
+            }
+        }
+
+        let mut runner = create_line_runner();
+        runner.add_sink(Failing);
+        runner.run().unwrap();
+        let failures: Vec<(u32, u32)> = runner
+            .take_failures()
+            .iter()
+            .map(|failure| (failure.node_id, failure.action_id))
+            .collect();
+        assert_eq!(failures, vec![(2, 2), (3, 3)]);
+
+        let mut runner =
+            LiveRunner::with_clock(MarkovChain::new(None, None), SimulatedClock::new());
+        assert_eq!(runner.poll(), Err(MarkovChainError::NoCurrentNodeError));
+    }
+}
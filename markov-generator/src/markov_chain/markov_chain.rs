@@ -1,188 +1,1074 @@
-use crate::markov_chain::{action::Action, edge::Edge, node::Node};
+use crate::markov_chain::{
+    action::{GenericAction, Trigger},
+    alias::AliasTable,
+    edge::GenericEdge,
+    names::NodeRef,
+    node::{ActionSelection, GenericNode},
+    online::{LearnerData, OnlineLearner},
+    schema::ActionSchema,
+    walk::{Step, Walk},
+    MarkovChainError,
+};
 use rand::prelude::*;
-use serde::{Deserialize, Serialize};
-
-#[derive(Debug, PartialEq)]
-pub enum MarkovChainError {
-    StuckError,
-    NodeDoesNotExistError,
-    EdgeDoesNotExistError,
-    ActionDoesNotExistError,
-    NodeHasNoEdgesError,
-    TransitionFailedError,
+use rand_chacha::ChaCha8Rng;
+use serde::{de::DeserializeOwned, Deserialize, Serialize, Serializer};
+use serde_json::Value;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Debug;
+use std::hash::Hash;
+
+/// How [`MarkovChain::next`] picks an outgoing edge.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplingMethod {
+    /// Sum the outgoing weights and walk them cumulatively on every step.
+    #[default]
+    Cumulative,
+    /// Compile an alias table per node on first use, so each step is O(1).
+    /// A node's table is rebuilt after its outgoing edges change.
+    Alias,
+}
+
+impl SamplingMethod {
+    fn is_cumulative(&self) -> bool {
+        *self == SamplingMethod::Cumulative
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct MarkovChain {
-    nodes: Vec<Node>,
-    edges: Vec<Edge>,
-    current_node: Option<u32>,
+/// What [`MarkovChain::try_add_edge`] does when an edge between the same two
+/// nodes already exists.
+///
+/// Unless the edge is rejected, the actions of both edges are merged: the new
+/// edge's actions are added to the existing ones, replacing any with the same
+/// id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicateEdgePolicy {
+    /// Fail with `EdgeAlreadyExistsError` and leave the chain unchanged.
+    Reject,
+    /// Replace the existing edge's weight.
+    Replace,
+    /// Add the new weight to the existing edge's weight.
+    AddWeight,
 }
 
-impl MarkovChain {
-    pub fn new(nodes: Option<Vec<Node>>, edges: Option<Vec<Edge>>) -> MarkovChain {
-        MarkovChain {
-            nodes: nodes.unwrap_or_default(),
-            edges: edges.unwrap_or_default(),
+/// A weighted directed graph of nodes that can be walked at random.
+///
+/// Nodes are indexed by id, and every node id keeps its own outgoing and
+/// incoming adjacency list, so lookups and transitions only touch the edges
+/// of the node involved rather than the whole graph. Edges may reference ids
+/// that have no node yet.
+///
+/// The plain mutators are lenient: `add_node`, `add_nodes` and `add_edge`
+/// replace or duplicate what is there, edges and terminal nodes may name ids
+/// that have no node, and `add_node_actions` and `add_edge_actions` do
+/// nothing for a missing node or edge. Each has a `try_` counterpart that
+/// fails instead.
+///
+/// Node ids can be any `Hash + Eq + Clone` type `I` and action payloads any
+/// type `V`. The [`MarkovChain`] alias uses `u32` ids and JSON payloads, which
+/// is what the analysis, fitting and text modules work with. Serializing
+/// additionally needs `I: Ord` so the output is deterministic.
+///
+/// A chain can own a seeded RNG (see [`GenericMarkovChain::set_seed`]). Its
+/// state is serialized together with `current_node` and the sampling method,
+/// so a paused walk resumes on the same sequence after a round trip.
+#[derive(Deserialize, Debug)]
+#[serde(
+    try_from = "ChainData<I, V>",
+    bound(deserialize = "I: Hash + Eq + Clone + Debug + Deserialize<'de>, \
+                         V: Clone + Deserialize<'de>")
+)]
+pub struct GenericMarkovChain<I, V> {
+    nodes: HashMap<I, GenericNode<I, V>>,
+    names: HashMap<String, I>,
+    outgoing: HashMap<I, Vec<GenericEdge<I, V>>>,
+    incoming: HashMap<I, Vec<I>>,
+    current_node: Option<I>,
+    terminal_nodes: HashSet<I>,
+    rng: Option<ChaCha8Rng>,
+    sampling_method: SamplingMethod,
+    alias_tables: HashMap<I, AliasTable>,
+    learner: OnlineLearner<I>,
+    action_schemas: HashMap<u32, ActionSchema>,
+    action_cursors: HashMap<(I, Trigger), usize>,
+}
+
+/// A chain with `u32` node ids and JSON action payloads.
+pub type MarkovChain = GenericMarkovChain<u32, Value>;
+
+impl<I, V> Default for GenericMarkovChain<I, V> {
+    fn default() -> Self {
+        GenericMarkovChain {
+            nodes: HashMap::new(),
+            names: HashMap::new(),
+            outgoing: HashMap::new(),
+            incoming: HashMap::new(),
             current_node: None,
+            terminal_nodes: HashSet::new(),
+            rng: None,
+            sampling_method: SamplingMethod::default(),
+            alias_tables: HashMap::new(),
+            learner: OnlineLearner::default(),
+            action_schemas: HashMap::new(),
+            action_cursors: HashMap::new(),
+        }
+    }
+}
+
+/// The flat `{ nodes, edges, current_node }` layout used for serialization.
+#[derive(Deserialize)]
+pub(crate) struct ChainData<I, V> {
+    pub(crate) nodes: Vec<GenericNode<I, V>>,
+    edges: Vec<EdgeData<I, V>>,
+    current_node: Option<I>,
+    #[serde(default = "Vec::new")]
+    terminal_nodes: Vec<I>,
+    #[serde(default)]
+    rng: Option<ChaCha8Rng>,
+    #[serde(default)]
+    sampling_method: SamplingMethod,
+    #[serde(default = "Option::default")]
+    learner: Option<LearnerData<I>>,
+    #[serde(default)]
+    action_schemas: HashMap<u32, ActionSchema>,
+    #[serde(default = "Vec::new")]
+    action_cursors: Vec<(I, Trigger, usize)>,
+}
+
+/// A serialized edge, whose endpoints may be given by id or by name.
+#[derive(Deserialize)]
+struct EdgeData<I, V> {
+    from: NodeRef<I>,
+    to: NodeRef<I>,
+    weight: f32,
+    #[serde(default = "Vec::new")]
+    actions: Vec<GenericAction<V>>,
+}
+
+#[derive(Serialize)]
+struct ChainDataRef<'a, I, V> {
+    nodes: Vec<&'a GenericNode<I, V>>,
+    edges: Vec<&'a GenericEdge<I, V>>,
+    current_node: Option<&'a I>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    terminal_nodes: Vec<&'a I>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rng: Option<&'a ChaCha8Rng>,
+    #[serde(skip_serializing_if = "SamplingMethod::is_cumulative")]
+    sampling_method: SamplingMethod,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    learner: Option<LearnerData<&'a I>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    action_schemas: BTreeMap<&'a u32, &'a ActionSchema>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    action_cursors: Vec<(&'a I, Trigger, usize)>,
+}
+
+impl<I, V> TryFrom<ChainData<I, V>> for GenericMarkovChain<I, V>
+where
+    I: Hash + Eq + Clone,
+    V: Clone,
+{
+    type Error = MarkovChainError<I>;
+
+    fn try_from(data: ChainData<I, V>) -> Result<Self, Self::Error> {
+        let mut mc = GenericMarkovChain::new(Some(data.nodes), None);
+        for edge in data.edges {
+            let from = mc.resolve(&edge.from)?;
+            let to = mc.resolve(&edge.to)?;
+            mc.add_edge(GenericEdge {
+                actions: edge.actions,
+                ..GenericEdge::new(from, to, edge.weight)
+            });
+        }
+        mc.current_node = data.current_node;
+        mc.terminal_nodes = data.terminal_nodes.into_iter().collect();
+        mc.rng = data.rng;
+        mc.sampling_method = data.sampling_method;
+        mc.learner = data.learner.map(OnlineLearner::from).unwrap_or_default();
+        mc.action_schemas = data.action_schemas;
+        mc.action_cursors = data
+            .action_cursors
+            .into_iter()
+            .map(|(node_id, trigger, cursor)| ((node_id, trigger), cursor))
+            .collect();
+        Ok(mc)
+    }
+}
+
+impl<I, V> Serialize for GenericMarkovChain<I, V>
+where
+    I: Hash + Eq + Ord + Serialize,
+    V: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut nodes: Vec<&GenericNode<I, V>> = self.nodes.values().collect();
+        nodes.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut sources: Vec<&I> = self.outgoing.keys().collect();
+        sources.sort();
+        let edges = sources
+            .into_iter()
+            .flat_map(|from| self.outgoing[from].iter())
+            .collect();
+
+        let mut terminal_nodes: Vec<&I> = self.terminal_nodes.iter().collect();
+        terminal_nodes.sort();
+
+        let mut action_cursors: Vec<(&I, Trigger, usize)> = self
+            .action_cursors
+            .iter()
+            .map(|((node_id, trigger), &cursor)| (node_id, *trigger, cursor))
+            .collect();
+        action_cursors.sort();
+
+        ChainDataRef {
+            nodes,
+            edges,
+            current_node: self.current_node.as_ref(),
+            terminal_nodes,
+            rng: self.rng.as_ref(),
+            sampling_method: self.sampling_method,
+            learner: self.learner.to_data(),
+            action_schemas: self.action_schemas.iter().collect(),
+            action_cursors,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<I, V> GenericMarkovChain<I, V>
+where
+    I: Hash + Eq + Clone + Debug + DeserializeOwned,
+    V: Clone + DeserializeOwned,
+{
+    /// Deserializes a chain from the JSON produced by serializing one.
+    pub fn from_json(json: &str) -> Result<Self, MarkovChainError<I>> {
+        serde_json::from_str(json).map_err(MarkovChainError::DeserializationError)
+    }
+}
+
+impl<I, V> GenericMarkovChain<I, V>
+where
+    I: Hash + Eq + Clone,
+    V: Clone,
+{
+    pub fn new(
+        nodes: Option<Vec<GenericNode<I, V>>>,
+        edges: Option<Vec<GenericEdge<I, V>>>,
+    ) -> Self {
+        let mut mc = GenericMarkovChain::default();
+        mc.add_nodes(&nodes.unwrap_or_default());
+        for edge in edges.unwrap_or_default() {
+            mc.add_edge(edge);
         }
+        mc
     }
 
-    pub fn add_node(&mut self, node: Node) {
-        self.nodes.push(node);
+    /// Adds a node, replacing any existing node with the same id. If another
+    /// node has the same name, that node loses its name to this one.
+    pub fn add_node(&mut self, node: GenericNode<I, V>) {
+        self.unindex_name(&node.id);
+        if let Some(name) = node.name.as_ref() {
+            if let Some(previous) = self.names.insert(name.clone(), node.id.clone()) {
+                if let Some(previous) = self.nodes.get_mut(&previous) {
+                    previous.name = None;
+                }
+            }
+        }
+        self.nodes.insert(node.id.clone(), node);
     }
 
-    pub fn add_nodes(&mut self, nodes: &[Node]) {
-        self.nodes.extend_from_slice(nodes);
+    pub(crate) fn names(&self) -> &HashMap<String, I> {
+        &self.names
     }
 
-    pub fn add_edge(&mut self, edge: Edge) {
-        self.edges.push(edge);
+    fn unindex_name(&mut self, node_id: &I) {
+        let name = self.nodes.get(node_id).and_then(|node| node.name.as_ref());
+        if let Some(name) = name {
+            if self.names.get(name) == Some(node_id) {
+                self.names.remove(name);
+            }
+        }
+    }
+
+    pub fn add_nodes(&mut self, nodes: &[GenericNode<I, V>]) {
+        for node in nodes {
+            self.add_node(node.clone());
+        }
+    }
+
+    pub fn add_edge(&mut self, edge: GenericEdge<I, V>) {
+        self.alias_tables.remove(&edge.from);
+        self.incoming
+            .entry(edge.to.clone())
+            .or_default()
+            .push(edge.from.clone());
+        self.outgoing
+            .entry(edge.from.clone())
+            .or_default()
+            .push(edge);
+    }
+
+    pub fn remove_node(&mut self, node_id: I) {
+        self.unindex_name(&node_id);
+        self.nodes.remove(&node_id);
+    }
+
+    pub fn remove_edge(&mut self, from_node_id: I, to_node_id: I) {
+        self.alias_tables.remove(&from_node_id);
+        if let Some(edges) = self.outgoing.get_mut(&from_node_id) {
+            edges.retain(|edge| edge.to != to_node_id);
+            if edges.is_empty() {
+                self.outgoing.remove(&from_node_id);
+            }
+        }
+
+        if let Some(sources) = self.incoming.get_mut(&to_node_id) {
+            sources.retain(|from| *from != from_node_id);
+            if sources.is_empty() {
+                self.incoming.remove(&to_node_id);
+            }
+        }
     }
 
-    pub fn remove_node(&mut self, node_id: u32) {
-        self.nodes.retain(|node| node.id != node_id);
+    /// Adds a node, failing if its id or name is already taken.
+    pub fn try_add_node(&mut self, node: GenericNode<I, V>) -> Result<(), MarkovChainError<I>> {
+        if self.nodes.contains_key(&node.id) {
+            return Err(MarkovChainError::NodeAlreadyExistsError { node_id: node.id });
+        }
+        if let Some(name) = node
+            .name
+            .as_ref()
+            .filter(|name| self.names.contains_key(*name))
+        {
+            return Err(MarkovChainError::NameAlreadyExistsError { name: name.clone() });
+        }
+        self.add_node(node);
+        Ok(())
     }
 
-    pub fn remove_edge(&mut self, from_node_id: u32, to_node_id: u32) {
-        self.edges
-            .retain(|edge| !(edge.from == from_node_id && edge.to == to_node_id));
+    /// Adds several nodes, failing without adding any if an id or name is
+    /// already taken or appears twice in `nodes`.
+    pub fn try_add_nodes(
+        &mut self,
+        nodes: &[GenericNode<I, V>],
+    ) -> Result<(), MarkovChainError<I>> {
+        let mut node_ids = HashSet::new();
+        let mut names = HashSet::new();
+        for node in nodes {
+            if self.nodes.contains_key(&node.id) || !node_ids.insert(&node.id) {
+                return Err(MarkovChainError::NodeAlreadyExistsError {
+                    node_id: node.id.clone(),
+                });
+            }
+            if let Some(name) = node.name.as_ref() {
+                if self.names.contains_key(name) || !names.insert(name) {
+                    return Err(MarkovChainError::NameAlreadyExistsError { name: name.clone() });
+                }
+            }
+        }
+        self.add_nodes(nodes);
+        Ok(())
     }
 
-    pub fn get_node(&self, node_id: u32) -> Option<&Node> {
-        self.nodes.iter().find(|node| node.id == node_id)
+    /// Adds an edge between two existing nodes. The weight must be finite and
+    /// non-negative, and `policy` decides what happens to an existing edge
+    /// between the same nodes.
+    pub fn try_add_edge(
+        &mut self,
+        edge: GenericEdge<I, V>,
+        policy: DuplicateEdgePolicy,
+    ) -> Result<(), MarkovChainError<I>> {
+        for node_id in [&edge.from, &edge.to] {
+            if !self.nodes.contains_key(node_id) {
+                return Err(MarkovChainError::NodeDoesNotExistError {
+                    node_id: node_id.clone(),
+                });
+            }
+        }
+        if !edge.weight.is_finite() || edge.weight < 0.0 {
+            return Err(MarkovChainError::InvalidWeightError {
+                from: edge.from,
+                to: edge.to,
+                weight: edge.weight,
+            });
+        }
+
+        if self.edge_exists(edge.from.clone(), edge.to.clone()) {
+            match policy {
+                DuplicateEdgePolicy::Reject => {
+                    return Err(MarkovChainError::EdgeAlreadyExistsError {
+                        from: edge.from,
+                        to: edge.to,
+                    })
+                }
+                DuplicateEdgePolicy::Replace => {
+                    let mut actions: Vec<GenericAction<V>> = self
+                        .outgoing_edges(edge.from.clone())
+                        .iter()
+                        .filter(|e| e.to == edge.to)
+                        .flat_map(|e| e.actions.iter().cloned())
+                        .collect();
+                    merge_actions(&mut actions, edge.actions);
+                    self.remove_edge(edge.from.clone(), edge.to.clone());
+                    self.add_edge(GenericEdge { actions, ..edge });
+                    return Ok(());
+                }
+                DuplicateEdgePolicy::AddWeight => {
+                    let Some(existing) = self
+                        .outgoing
+                        .get_mut(&edge.from)
+                        .and_then(|edges| edges.iter_mut().find(|e| e.to == edge.to))
+                    else {
+                        return Err(MarkovChainError::EdgeDoesNotExistError {
+                            from: edge.from,
+                            to: edge.to,
+                        });
+                    };
+                    let weight = existing.weight + edge.weight;
+                    if !weight.is_finite() {
+                        return Err(MarkovChainError::InvalidWeightError {
+                            from: edge.from,
+                            to: edge.to,
+                            weight,
+                        });
+                    }
+                    existing.weight = weight;
+                    merge_actions(&mut existing.actions, edge.actions);
+                    self.alias_tables.remove(&edge.from);
+                    return Ok(());
+                }
+            }
+        }
+
+        self.add_edge(edge);
+        Ok(())
     }
 
-    pub fn get_edge(&self, from_node_id: u32, to_node_id: u32) -> Option<&Edge> {
-        self.edges
+    /// Removes a node together with every edge entering or leaving it. If the
+    /// node was current or terminal, it stops being so.
+    pub fn try_remove_node(
+        &mut self,
+        node_id: I,
+    ) -> Result<GenericNode<I, V>, MarkovChainError<I>> {
+        self.unindex_name(&node_id);
+        let Some(node) = self.nodes.remove(&node_id) else {
+            return Err(MarkovChainError::NodeDoesNotExistError { node_id });
+        };
+
+        let targets: Vec<I> = self
+            .outgoing_edges(node_id.clone())
             .iter()
-            .find(|edge| edge.from == from_node_id && edge.to == to_node_id)
+            .map(|e| e.to.clone())
+            .collect();
+        for to in targets {
+            self.remove_edge(node_id.clone(), to);
+        }
+        for from in self.incoming_nodes(node_id.clone()).to_vec() {
+            self.remove_edge(from, node_id.clone());
+        }
+
+        self.terminal_nodes.remove(&node_id);
+        self.action_cursors.retain(|(id, _), _| *id != node_id);
+        if self.current_node.as_ref() == Some(&node_id) {
+            self.current_node = None;
+        }
+
+        Ok(node)
     }
 
-    pub fn add_node_actions(&mut self, node_id: u32, actions: &[Action]) {
-        if let Some(node) = self.nodes.iter_mut().find(|node| node.id == node_id) {
-            node.actions.extend_from_slice(actions);
+    /// Removes every edge from `from_node_id` to `to_node_id`, failing if
+    /// there is none.
+    pub fn try_remove_edge(
+        &mut self,
+        from_node_id: I,
+        to_node_id: I,
+    ) -> Result<(), MarkovChainError<I>> {
+        if !self.edge_exists(from_node_id.clone(), to_node_id.clone()) {
+            return Err(MarkovChainError::EdgeDoesNotExistError {
+                from: from_node_id,
+                to: to_node_id,
+            });
         }
+        self.remove_edge(from_node_id, to_node_id);
+        Ok(())
     }
 
-    pub fn get_node_actions(&self, node_id: u32) -> Option<&Vec<Action>> {
-        self.nodes
+    /// Appends actions to an existing node. Fails without changing the node if
+    /// any action id would be duplicated.
+    pub fn try_add_node_actions(
+        &mut self,
+        node_id: I,
+        actions: &[GenericAction<V>],
+    ) -> Result<(), MarkovChainError<I>> {
+        let Some(node) = self.nodes.get_mut(&node_id) else {
+            return Err(MarkovChainError::NodeDoesNotExistError { node_id });
+        };
+
+        let mut action_ids: HashSet<u32> = node.actions.iter().map(|a| a.id).collect();
+        if let Some(action) = actions.iter().find(|action| !action_ids.insert(action.id)) {
+            return Err(MarkovChainError::ActionAlreadyExistsError {
+                node_id,
+                action_id: action.id,
+            });
+        }
+
+        node.actions.extend_from_slice(actions);
+        Ok(())
+    }
+
+    /// Appends actions to an existing edge. Fails without changing the edge if
+    /// any action id would be duplicated.
+    pub fn try_add_edge_actions(
+        &mut self,
+        from_node_id: I,
+        to_node_id: I,
+        actions: &[GenericAction<V>],
+    ) -> Result<(), MarkovChainError<I>> {
+        let edge = self
+            .outgoing
+            .get_mut(&from_node_id)
+            .and_then(|edges| edges.iter_mut().find(|edge| edge.to == to_node_id));
+        let Some(edge) = edge else {
+            return Err(MarkovChainError::EdgeDoesNotExistError {
+                from: from_node_id,
+                to: to_node_id,
+            });
+        };
+
+        let mut action_ids: HashSet<u32> = edge.actions.iter().map(|a| a.id).collect();
+        if let Some(action) = actions.iter().find(|action| !action_ids.insert(action.id)) {
+            return Err(MarkovChainError::EdgeActionAlreadyExistsError {
+                from: from_node_id,
+                to: to_node_id,
+                action_id: action.id,
+            });
+        }
+
+        edge.actions.extend_from_slice(actions);
+        Ok(())
+    }
+
+    /// Marks an existing node as terminal.
+    pub fn try_add_terminal_node(&mut self, node_id: I) -> Result<(), MarkovChainError<I>> {
+        if !self.nodes.contains_key(&node_id) {
+            return Err(MarkovChainError::NodeDoesNotExistError { node_id });
+        }
+        self.add_terminal_node(node_id);
+        Ok(())
+    }
+
+    /// Sets which actions fire when a walk enters `node_id`.
+    pub fn set_action_selection(
+        &mut self,
+        node_id: I,
+        selection: ActionSelection,
+    ) -> Result<(), MarkovChainError<I>> {
+        let Some(node) = self.nodes.get_mut(&node_id) else {
+            return Err(MarkovChainError::NodeDoesNotExistError { node_id });
+        };
+        node.selection = selection;
+        Ok(())
+    }
+
+    pub fn get_node(&self, node_id: I) -> Option<&GenericNode<I, V>> {
+        self.nodes.get(&node_id)
+    }
+
+    /// Iterates over all nodes in no particular order.
+    pub fn nodes(&self) -> impl Iterator<Item = &GenericNode<I, V>> {
+        self.nodes.values()
+    }
+
+    /// Iterates over all edges in no particular order.
+    pub fn edges(&self) -> impl Iterator<Item = &GenericEdge<I, V>> {
+        self.outgoing.values().flatten()
+    }
+
+    /// Returns the weight of the first edge from `from_node_id` to
+    /// `to_node_id`, adding a zero-weight edge if there is none.
+    pub(crate) fn edge_weight_mut(&mut self, from_node_id: I, to_node_id: I) -> &mut f32 {
+        if !self.edge_exists(from_node_id.clone(), to_node_id.clone()) {
+            self.add_edge(GenericEdge::new(
+                from_node_id.clone(),
+                to_node_id.clone(),
+                0.0,
+            ));
+        }
+        self.alias_tables.remove(&from_node_id);
+
+        let edges = self.outgoing.get_mut(&from_node_id).unwrap();
+        let edge = edges.iter_mut().find(|edge| edge.to == to_node_id).unwrap();
+        &mut edge.weight
+    }
+
+    pub(crate) fn learner(&self) -> &OnlineLearner<I> {
+        &self.learner
+    }
+
+    pub(crate) fn learner_mut(&mut self) -> &mut OnlineLearner<I> {
+        &mut self.learner
+    }
+
+    pub(crate) fn action_schemas(&self) -> &HashMap<u32, ActionSchema> {
+        &self.action_schemas
+    }
+
+    pub(crate) fn action_schemas_mut(&mut self) -> &mut HashMap<u32, ActionSchema> {
+        &mut self.action_schemas
+    }
+
+    pub fn get_edge(&self, from_node_id: I, to_node_id: I) -> Option<&GenericEdge<I, V>> {
+        self.outgoing_edges(from_node_id)
             .iter()
-            .find(|node| node.id == node_id)
-            .map(|node| &node.actions)
+            .find(|edge| edge.to == to_node_id)
+    }
+
+    pub fn add_node_actions(&mut self, node_id: I, actions: &[GenericAction<V>]) {
+        if let Some(node) = self.nodes.get_mut(&node_id) {
+            node.actions.extend_from_slice(actions);
+        }
+    }
+
+    /// Appends actions to the edge from `from_node_id` to `to_node_id`, if
+    /// there is one.
+    pub fn add_edge_actions(
+        &mut self,
+        from_node_id: I,
+        to_node_id: I,
+        actions: &[GenericAction<V>],
+    ) {
+        let edge = self
+            .outgoing
+            .get_mut(&from_node_id)
+            .and_then(|edges| edges.iter_mut().find(|edge| edge.to == to_node_id));
+        if let Some(edge) = edge {
+            edge.actions.extend_from_slice(actions);
+        }
+    }
+
+    pub fn get_edge_actions(
+        &self,
+        from_node_id: I,
+        to_node_id: I,
+    ) -> Option<&Vec<GenericAction<V>>> {
+        self.get_edge(from_node_id, to_node_id)
+            .map(|edge| &edge.actions)
     }
 
-    pub fn get_node_action(&self, node_id: u32, action_id: u32) -> Option<&Action> {
+    pub fn get_node_actions(&self, node_id: I) -> Option<&Vec<GenericAction<V>>> {
+        self.nodes.get(&node_id).map(|node| &node.actions)
+    }
+
+    pub fn get_node_action(&self, node_id: I, action_id: u32) -> Option<&GenericAction<V>> {
         self.nodes
-            .iter()
-            .find(|node| node.id == node_id)
+            .get(&node_id)
             .and_then(|node| node.actions.iter().find(|action| action.id == action_id))
     }
 
-    pub fn get_edge_from(&self, node_id: u32) -> Option<&u32> {
-        self.edges
-            .iter()
-            .find(|edge| edge.from == node_id)
-            .map(|edge| &edge.from)
+    pub fn get_edge_from(&self, node_id: I) -> Option<&I> {
+        self.outgoing_edges(node_id).first().map(|edge| &edge.from)
     }
 
-    pub fn get_edge_to(&self, node_id: u32) -> Option<&u32> {
-        self.edges
-            .iter()
-            .find(|edge| edge.to == node_id)
+    pub fn get_edge_to(&self, node_id: I) -> Option<&I> {
+        self.incoming_nodes(node_id.clone())
+            .first()
+            .and_then(|from| self.get_edge(from.clone(), node_id))
             .map(|edge| &edge.to)
     }
 
-    pub fn get_node_edges(&self, node_id: u32) -> Result<Vec<Edge>, MarkovChainError> {
-        if !self.node_exists(node_id) {
-            return Err(MarkovChainError::NodeDoesNotExistError);
+    pub fn get_node_edges(
+        &self,
+        node_id: I,
+    ) -> Result<Vec<GenericEdge<I, V>>, MarkovChainError<I>> {
+        if !self.nodes.contains_key(&node_id) {
+            return Err(MarkovChainError::NodeDoesNotExistError { node_id });
         }
 
-        let edges: Vec<Edge> = self
-            .edges
-            .iter()
-            .filter(|edge| edge.from == node_id)
-            .cloned()
-            .collect();
+        Ok(self.outgoing_edges(node_id).to_vec())
+    }
 
-        Ok(edges)
+    /// Returns the edges leaving `node_id`, in insertion order.
+    pub fn outgoing_edges(&self, node_id: I) -> &[GenericEdge<I, V>] {
+        self.outgoing.get(&node_id).map_or(&[], Vec::as_slice)
     }
 
-    pub fn node_exists(&self, node_id: u32) -> bool {
-        self.nodes.iter().any(|node| node.id == node_id)
+    /// Returns the source node id of every edge entering `node_id`.
+    pub fn incoming_nodes(&self, node_id: I) -> &[I] {
+        self.incoming.get(&node_id).map_or(&[], Vec::as_slice)
     }
 
-    pub fn edge_exists(&self, from_node_id: u32, to_node_id: u32) -> bool {
-        self.edges
-            .iter()
-            .any(|edge| edge.from == from_node_id && edge.to == to_node_id)
+    pub fn node_exists(&self, node_id: I) -> bool {
+        self.nodes.contains_key(&node_id)
     }
 
-    pub fn set_current_node(&mut self, node_id: u32) -> Result<(), MarkovChainError> {
-        if let Some(_) = self.nodes.iter().find(|node| node.id == node_id) {
+    pub fn edge_exists(&self, from_node_id: I, to_node_id: I) -> bool {
+        self.get_edge(from_node_id, to_node_id).is_some()
+    }
+
+    pub fn set_current_node(&mut self, node_id: I) -> Result<(), MarkovChainError<I>> {
+        if self.nodes.contains_key(&node_id) {
             self.current_node = Some(node_id);
             Ok(())
         } else {
-            Err(MarkovChainError::NodeDoesNotExistError)
+            Err(MarkovChainError::NodeDoesNotExistError { node_id })
         }
     }
 
-    pub fn get_current_node(&self) -> Option<u32> {
-        self.current_node
+    pub fn get_current_node(&self) -> Option<I> {
+        self.current_node.clone()
     }
 
-    pub fn next<'a>(&'a mut self) -> Result<(), MarkovChainError> {
-        let mut rng = rand::thread_rng();
-        if self.current_node.is_none() {
-            return Err(MarkovChainError::NodeDoesNotExistError);
+    pub fn set_sampling_method(&mut self, sampling_method: SamplingMethod) {
+        if sampling_method != SamplingMethod::Alias {
+            self.alias_tables.clear();
         }
+        self.sampling_method = sampling_method;
+    }
+
+    pub fn get_sampling_method(&self) -> SamplingMethod {
+        self.sampling_method
+    }
+
+    /// Marks `node_id` as terminal, so walks stop once they enter it.
+    pub fn add_terminal_node(&mut self, node_id: I) {
+        self.terminal_nodes.insert(node_id);
+    }
+
+    pub fn remove_terminal_node(&mut self, node_id: I) {
+        self.terminal_nodes.remove(&node_id);
+    }
+
+    pub fn is_terminal_node(&self, node_id: I) -> bool {
+        self.terminal_nodes.contains(&node_id)
+    }
+
+    /// Seeds the chain's own RNG, which [`GenericMarkovChain::next`] uses from
+    /// then on.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Some(ChaCha8Rng::seed_from_u64(seed));
+    }
+
+    /// Drops the chain's own RNG so [`GenericMarkovChain::next`] goes back to
+    /// `rand::thread_rng()`.
+    pub fn clear_seed(&mut self) {
+        self.rng = None;
+    }
+
+    /// Moves to a random neighbour of the current node, using the chain's
+    /// seeded RNG if it has one and `rand::thread_rng()` otherwise.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<(), MarkovChainError<I>> {
+        self.with_rng(|mc, rng| mc.next_with_rng(rng))
+    }
+
+    /// Moves to a random neighbour of the current node, drawing from `rng`.
+    pub fn next_with_rng<R: Rng + ?Sized>(
+        &mut self,
+        rng: &mut R,
+    ) -> Result<(), MarkovChainError<I>> {
+        self.advance(rng).map(|_| ())
+    }
+
+    /// Like [`GenericMarkovChain::next`], but returns a record of the
+    /// transition.
+    pub fn step(&mut self) -> Result<Step<I, V>, MarkovChainError<I>> {
+        self.with_rng(|mc, rng| mc.step_with_rng(rng))
+    }
+
+    /// Like [`GenericMarkovChain::next_with_rng`], but returns a record of the
+    /// transition. The record lists every action with a matching [`Trigger`];
+    /// node selection policies are only applied by
+    /// [`GenericMarkovChain::step_and_act`].
+    pub fn step_with_rng<R: Rng + ?Sized>(
+        &mut self,
+        rng: &mut R,
+    ) -> Result<Step<I, V>, MarkovChainError<I>> {
+        let (from, edge_index) = self.advance(rng)?;
+        let edge = &self.outgoing[&from][edge_index];
+
+        let (exit_actions, actions) = if edge.to == from {
+            (Vec::new(), self.triggered_actions(&from, Trigger::Stay))
+        } else {
+            (
+                self.triggered_actions(&from, Trigger::Exit),
+                self.triggered_actions(&edge.to, Trigger::Enter),
+            )
+        };
+
+        Ok(Step {
+            to: edge.to.clone(),
+            weight: edge.weight,
+            exit_actions,
+            edge_actions: edge.actions.clone(),
+            actions,
+            from,
+        })
+    }
+
+    /// Moves to a random neighbour of the current node and returns the
+    /// actions that fire, in this order:
+    ///
+    /// 1. the old node's `Exit` actions,
+    /// 2. the actions of the edge taken,
+    /// 3. the new node's `Enter` actions.
+    ///
+    /// On a self-loop the edge's actions are followed by the node's `Stay`
+    /// actions instead. Each node's [`ActionSelection`] is applied separately
+    /// to each group of its actions, and random picks are drawn in the order
+    /// above.
+    pub fn step_and_act(&mut self) -> Result<Vec<GenericAction<V>>, MarkovChainError<I>> {
+        self.with_rng(|mc, rng| mc.step_and_act_with_rng(rng))
+    }
+
+    /// Like [`GenericMarkovChain::step_and_act`], but draws from `rng`.
+    pub fn step_and_act_with_rng<R: Rng + ?Sized>(
+        &mut self,
+        rng: &mut R,
+    ) -> Result<Vec<GenericAction<V>>, MarkovChainError<I>> {
+        let events = self.fire_with_rng(rng)?;
+        Ok(events.into_iter().map(|(_, action)| action).collect())
+    }
+
+    /// Like [`GenericMarkovChain::step_and_act`], but pairs each action with
+    /// the node it fired for. Edge actions are paired with the node that was
+    /// left.
+    pub(crate) fn fire(&mut self) -> Result<Vec<(I, GenericAction<V>)>, MarkovChainError<I>> {
+        self.with_rng(|mc, rng| mc.fire_with_rng(rng))
+    }
 
-        let current_node_id = self.current_node.unwrap();
+    fn fire_with_rng<R: Rng + ?Sized>(
+        &mut self,
+        rng: &mut R,
+    ) -> Result<Vec<(I, GenericAction<V>)>, MarkovChainError<I>> {
+        let (from, edge_index) = self.advance(rng)?;
+        let edge = &self.outgoing[&from][edge_index];
+        let edge_actions = edge.actions.clone();
+        let to = edge.to.clone();
 
-        let edges = match self.get_node_edges(current_node_id) {
-            Ok(edges) => edges,
-            Err(_) => return Err(MarkovChainError::NodeHasNoEdgesError),
+        let tagged = |node_id: &I, actions: Vec<GenericAction<V>>| {
+            actions
+                .into_iter()
+                .map(|action| (node_id.clone(), action))
+                .collect::<Vec<_>>()
         };
 
-        if edges.is_empty() {
-            return Err(MarkovChainError::NodeHasNoEdgesError);
+        if to == from {
+            let mut events = tagged(&from, edge_actions);
+            let stay = self.select_actions(from.clone(), Trigger::Stay, rng);
+            events.extend(tagged(&from, stay));
+            return Ok(events);
         }
 
-        let mut total_weight = 0.0;
-        for edge in edges.iter() {
-            total_weight += edge.weight;
+        let exit = self.select_actions(from.clone(), Trigger::Exit, rng);
+        let mut events = tagged(&from, exit);
+        events.extend(tagged(&from, edge_actions));
+        let enter = self.select_actions(to.clone(), Trigger::Enter, rng);
+        events.extend(tagged(&to, enter));
+        Ok(events)
+    }
+
+    /// The actions of `node_id` with the given trigger, in order.
+    fn triggered_actions(&self, node_id: &I, trigger: Trigger) -> Vec<GenericAction<V>> {
+        self.nodes
+            .get(node_id)
+            .into_iter()
+            .flat_map(|node| node.actions.iter())
+            .filter(|action| action.trigger == trigger)
+            .cloned()
+            .collect()
+    }
+
+    /// Picks which of the actions of `node_id` with the given trigger fire. A
+    /// weighted node whose action weights do not sum to a positive number
+    /// fires none.
+    fn select_actions<R: Rng + ?Sized>(
+        &mut self,
+        node_id: I,
+        trigger: Trigger,
+        rng: &mut R,
+    ) -> Vec<GenericAction<V>> {
+        let Some(node) = self.nodes.get(&node_id) else {
+            return Vec::new();
+        };
+        let actions: Vec<&GenericAction<V>> = node
+            .actions
+            .iter()
+            .filter(|action| action.trigger == trigger)
+            .collect();
+
+        let index = match node.selection {
+            ActionSelection::All => return actions.into_iter().cloned().collect(),
+            _ if actions.is_empty() => None,
+            ActionSelection::Uniform => Some(rng.gen_range(0..actions.len())),
+            ActionSelection::Weighted => {
+                sample_weighted(actions.iter().map(|action| action.weight), rng)
+            }
+            ActionSelection::RoundRobin => {
+                let cursor = self.action_cursors.entry((node_id, trigger)).or_default();
+                let index = *cursor % actions.len();
+                *cursor = index + 1;
+                Some(index)
+            }
+        };
+
+        index
+            .map(|index| actions[index].clone())
+            .into_iter()
+            .collect()
+    }
+
+    /// Starts a walk at `start` that takes at most `max_steps` steps.
+    ///
+    /// The walk moves `current_node` as it goes, and draws from the chain's
+    /// seeded RNG if it has one. See [`Walk`] for when it stops.
+    pub fn walk(
+        &mut self,
+        start: I,
+        max_steps: usize,
+    ) -> Result<Walk<'_, I, V>, MarkovChainError<I>> {
+        self.set_current_node(start)?;
+        Ok(Walk::new(self, max_steps))
+    }
+
+    /// Collects `n` independent walks, each starting at `start`.
+    pub fn walks(
+        &mut self,
+        n: usize,
+        start: I,
+        max_steps: usize,
+    ) -> Result<Vec<Vec<Step<I, V>>>, MarkovChainError<I>> {
+        (0..n)
+            .map(|_| self.walk(start.clone(), max_steps)?.collect())
+            .collect()
+    }
+
+    /// Runs `f` with the chain's seeded RNG, or `rand::thread_rng()` if it has
+    /// none.
+    fn with_rng<T>(&mut self, f: impl FnOnce(&mut Self, &mut dyn RngCore) -> T) -> T {
+        match self.rng.take() {
+            Some(mut rng) => {
+                let result = f(self, &mut rng);
+                self.rng = Some(rng);
+                result
+            }
+            None => f(self, &mut rand::thread_rng()),
         }
+    }
+
+    /// Moves to a random neighbour of the current node and returns the node
+    /// that was left along with the index of the edge that was taken.
+    fn advance<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Result<(I, usize), MarkovChainError<I>> {
+        let current_node_id = match &self.current_node {
+            Some(node_id) if self.nodes.contains_key(node_id) => node_id.clone(),
+            Some(node_id) => {
+                return Err(MarkovChainError::NodeDoesNotExistError {
+                    node_id: node_id.clone(),
+                })
+            }
+            None => return Err(MarkovChainError::NoCurrentNodeError),
+        };
+
+        let edges = match self.outgoing.get(&current_node_id) {
+            Some(edges) if !edges.is_empty() => edges,
+            _ => {
+                return Err(MarkovChainError::NodeHasNoEdgesError {
+                    node_id: current_node_id,
+                })
+            }
+        };
+
+        let edge_index = match self.sampling_method {
+            SamplingMethod::Cumulative => Self::sample_cumulative(edges, rng),
+            SamplingMethod::Alias => {
+                let table = match self.alias_tables.entry(current_node_id.clone()) {
+                    Entry::Occupied(entry) => Some(&*entry.into_mut()),
+                    Entry::Vacant(entry) => {
+                        let weights: Vec<f32> = edges.iter().map(|edge| edge.weight).collect();
+                        AliasTable::new(&weights).map(|table| &*entry.insert(table))
+                    }
+                };
+                table.map(|table| table.sample(rng))
+            }
+        };
 
-        let mut random_weight = rng.gen_range(0.0..total_weight);
-        for edge in edges.iter() {
-            if random_weight < edge.weight {
-                self.current_node = Some(edge.to);
-                return Ok(());
+        match edge_index {
+            Some(edge_index) => {
+                self.current_node = Some(edges[edge_index].to.clone());
+                Ok((current_node_id, edge_index))
             }
-            random_weight -= edge.weight;
+            None => Err(MarkovChainError::TransitionFailedError {
+                node_id: current_node_id,
+            }),
         }
+    }
 
-        Err(MarkovChainError::TransitionFailedError)
+    pub(crate) fn sample_cumulative<R: Rng + ?Sized>(
+        edges: &[GenericEdge<I, V>],
+        rng: &mut R,
+    ) -> Option<usize> {
+        sample_weighted(edges.iter().map(|edge| edge.weight), rng)
     }
 }
 
+/// Adds `incoming` to `actions`, replacing any action with the same id.
+fn merge_actions<V>(actions: &mut Vec<GenericAction<V>>, incoming: Vec<GenericAction<V>>) {
+    for action in incoming {
+        match actions.iter_mut().find(|existing| existing.id == action.id) {
+            Some(existing) => *existing = action,
+            None => actions.push(action),
+        }
+    }
+}
+
+/// Picks an index in proportion to `weights` by walking them cumulatively.
+/// Returns `None` if the weights do not sum to a finite, positive number.
+fn sample_weighted<R: Rng + ?Sized>(
+    weights: impl Iterator<Item = f32> + Clone,
+    rng: &mut R,
+) -> Option<usize> {
+    let total_weight: f32 = weights.clone().sum();
+    if !total_weight.is_finite() || total_weight <= 0.0 {
+        return None;
+    }
+
+    let mut random_weight = rng.gen_range(0.0..total_weight);
+    for (index, weight) in weights.enumerate() {
+        if random_weight < weight {
+            return Some(index);
+        }
+        random_weight -= weight;
+    }
+
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::markov_chain::{action::Action, edge::Edge, node::Node};
 
     fn create_test_nodes() -> Vec<Node> {
         vec![
             Node {
                 id: 1,
+                name: None,
                 actions: vec![],
+                selection: ActionSelection::All,
             },
             Node {
                 id: 2,
+                name: None,
                 actions: vec![],
+                selection: ActionSelection::All,
             },
             Node {
                 id: 3,
+                name: None,
                 actions: vec![],
+                selection: ActionSelection::All,
             },
         ]
     }
@@ -193,16 +1079,19 @@ mod tests {
                 from: 1,
                 to: 2,
                 weight: 1.0,
+                actions: vec![],
             },
             Edge {
                 from: 1,
                 to: 3,
                 weight: 1.0,
+                actions: vec![],
             },
             Edge {
                 from: 2,
                 to: 3,
                 weight: 1.0,
+                actions: vec![],
             },
         ]
     }
@@ -212,7 +1101,9 @@ mod tests {
         let mut mc = MarkovChain::new(None, None);
         let node = Node {
             id: 1,
+            name: None,
             actions: vec![],
+            selection: ActionSelection::All,
         };
         mc.add_node(node.clone());
         assert_eq!(mc.get_node(1), Some(&node));
@@ -242,6 +1133,7 @@ mod tests {
             from: 1,
             to: 2,
             weight: 1.0,
+            actions: vec![],
         };
         mc.add_edge(edge.clone());
         assert_eq!(mc.get_edge(1, 2), Some(&edge));
@@ -255,13 +1147,14 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn test_set_current_node() {
         let mut mc = MarkovChain::new(Some(create_test_nodes()), None);
 
         assert_eq!(mc.set_current_node(1), Ok(()));
         assert_eq!(
             mc.set_current_node(4),
-            Err(MarkovChainError::NodeDoesNotExistError)
+            Err(MarkovChainError::NodeDoesNotExistError { node_id: 4 })
         );
 
         match mc.get_current_node() {
@@ -273,7 +1166,7 @@ mod tests {
     #[test]
     fn test_next() {
         let mut mc = MarkovChain::new(Some(create_test_nodes()), Some(create_test_edges()));
-        assert_eq!(mc.next(), Err(MarkovChainError::NodeDoesNotExistError));
+        assert_eq!(mc.next(), Err(MarkovChainError::NoCurrentNodeError));
 
         mc.set_current_node(1).unwrap();
         assert!(mc.next().is_ok());
@@ -287,12 +1180,14 @@ mod tests {
         assert!(edges.contains(&Edge {
             from: 1,
             to: 2,
-            weight: 1.0
+            weight: 1.0,
+            actions: vec![],
         }));
         assert!(edges.contains(&Edge {
             from: 1,
             to: 3,
-            weight: 1.0
+            weight: 1.0,
+            actions: vec![],
         }));
     }
 
@@ -322,4 +1217,547 @@ mod tests {
         assert!((ratio_to_2 - expected_ratio).abs() < 0.05);
         assert!((ratio_to_3 - expected_ratio).abs() < 0.05);
     }
+
+    #[test]
+    fn test_adjacency_after_remove_edge() {
+        let mut mc = MarkovChain::new(Some(create_test_nodes()), Some(create_test_edges()));
+        assert_eq!(mc.incoming_nodes(3), &[1, 2]);
+
+        mc.remove_edge(1, 3);
+        assert_eq!(mc.outgoing_edges(1).len(), 1);
+        assert_eq!(mc.incoming_nodes(3), &[2]);
+        assert_eq!(mc.get_edge_to(3), Some(&3));
+        assert!(!mc.edge_exists(1, 3));
+    }
+
+    #[test]
+    fn test_serialization_round_trip() {
+        let mut mc = MarkovChain::new(Some(create_test_nodes()), Some(create_test_edges()));
+        mc.set_current_node(2).unwrap();
+
+        let serialized = serde_json::to_string(&mc).unwrap();
+        let deserialized: MarkovChain = serde_json::from_str(&serialized).unwrap();
+
+        assert_eq!(serde_json::to_string(&deserialized).unwrap(), serialized);
+        assert_eq!(deserialized.get_current_node(), Some(2));
+        assert_eq!(deserialized.get_node_edges(1).unwrap().len(), 2);
+        assert_eq!(deserialized.incoming_nodes(3), &[1, 2]);
+    }
+
+    #[test]
+    fn test_next_alias_distribution() {
+        let mut mc = MarkovChain::new(Some(create_test_nodes()), Some(create_test_edges()));
+        mc.set_sampling_method(SamplingMethod::Alias);
+        mc.add_edge(Edge::new(1, 1, 2.0));
+
+        let mut counts = HashMap::new();
+        let iterations = 10000;
+        for _ in 0..iterations {
+            mc.set_current_node(1).unwrap();
+            mc.next().unwrap();
+            *counts.entry(mc.get_current_node().unwrap()).or_insert(0) += 1;
+        }
+
+        let ratio = |node_id| counts[&node_id] as f64 / iterations as f64;
+        assert!((ratio(1) - 0.5).abs() < 0.05);
+        assert!((ratio(2) - 0.25).abs() < 0.05);
+        assert!((ratio(3) - 0.25).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_alias_table_rebuilt_after_edge_change() {
+        let mut mc = MarkovChain::new(Some(create_test_nodes()), Some(create_test_edges()));
+        mc.set_sampling_method(SamplingMethod::Alias);
+        mc.set_current_node(1).unwrap();
+        mc.next().unwrap();
+
+        mc.remove_edge(1, 2);
+        for _ in 0..100 {
+            mc.set_current_node(1).unwrap();
+            mc.next().unwrap();
+            assert_eq!(mc.get_current_node(), Some(3));
+        }
+    }
+
+    fn create_cycle_chain() -> MarkovChain {
+        let mut mc = MarkovChain::new(Some(create_test_nodes()), Some(create_test_edges()));
+        mc.add_edge(Edge::new(3, 1, 1.0));
+        mc.add_edge(Edge::new(3, 2, 1.0));
+        mc.set_current_node(1).unwrap();
+        mc
+    }
+
+    fn walk_ids(mc: &mut MarkovChain, steps: usize) -> Vec<u32> {
+        (0..steps)
+            .map(|_| {
+                mc.next().unwrap();
+                mc.get_current_node().unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_next_with_rng_is_reproducible() {
+        let mut a = create_cycle_chain();
+        let mut b = create_cycle_chain();
+        let mut rng_a = ChaCha8Rng::seed_from_u64(7);
+        let mut rng_b = ChaCha8Rng::seed_from_u64(7);
+
+        for _ in 0..50 {
+            a.next_with_rng(&mut rng_a).unwrap();
+            b.next_with_rng(&mut rng_b).unwrap();
+            assert_eq!(a.get_current_node(), b.get_current_node());
+        }
+    }
+
+    #[test]
+    fn test_seeded_walk_resumes_after_round_trip() {
+        for method in [SamplingMethod::Cumulative, SamplingMethod::Alias] {
+            let mut mc = create_cycle_chain();
+            mc.set_sampling_method(method);
+            mc.set_seed(42);
+            walk_ids(&mut mc, 10);
+
+            let serialized = serde_json::to_string(&mc).unwrap();
+            let mut resumed: MarkovChain = serde_json::from_str(&serialized).unwrap();
+
+            assert_eq!(resumed.get_sampling_method(), method);
+            assert_eq!(resumed.get_current_node(), mc.get_current_node());
+            assert_eq!(walk_ids(&mut resumed, 50), walk_ids(&mut mc, 50));
+        }
+    }
+
+    #[test]
+    fn test_unseeded_chain_omits_rng_state() {
+        let serialized = serde_json::to_string(&create_cycle_chain()).unwrap();
+        assert!(!serialized.contains("rng"));
+        assert!(!serialized.contains("sampling_method"));
+    }
+
+    #[test]
+    fn test_walk_stops_at_sink() {
+        let mut mc = MarkovChain::new(Some(create_test_nodes()), Some(create_test_edges()));
+        mc.add_node_actions(3, &[Action::new(1, None)]);
+
+        let steps: Vec<Step> = mc.walk(2, 10).unwrap().map(Result::unwrap).collect();
+        assert_eq!(
+            steps,
+            vec![Step {
+                from: 2,
+                to: 3,
+                weight: 1.0,
+                exit_actions: vec![],
+                edge_actions: vec![],
+                actions: vec![Action::new(1, None)],
+            }]
+        );
+        assert_eq!(mc.get_current_node(), Some(3));
+    }
+
+    #[test]
+    fn test_walk_stops_at_terminal_node_and_max_steps() {
+        let mut mc = create_cycle_chain();
+        assert_eq!(mc.walk(1, 25).unwrap().count(), 25);
+
+        mc.add_terminal_node(3);
+        for steps in mc.walks(20, 1, 100).unwrap() {
+            assert_eq!(steps.last().unwrap().to, 3);
+            assert!(steps[..steps.len() - 1].iter().all(|step| step.to != 3));
+        }
+
+        assert_eq!(mc.walk(3, 10).unwrap().count(), 0);
+        assert!(mc.walk(4, 10).is_err());
+    }
+
+    #[test]
+    fn test_try_add_node_and_edge() {
+        let mut mc = MarkovChain::new(Some(create_test_nodes()), None);
+        assert_eq!(
+            mc.try_add_node(Node::new(1, None)),
+            Err(MarkovChainError::NodeAlreadyExistsError { node_id: 1 })
+        );
+        assert_eq!(mc.try_add_node(Node::new(4, None)), Ok(()));
+
+        let policy = DuplicateEdgePolicy::Reject;
+        assert_eq!(
+            mc.try_add_edge(Edge::new(1, 5, 1.0), policy),
+            Err(MarkovChainError::NodeDoesNotExistError { node_id: 5 })
+        );
+        assert_eq!(
+            mc.try_add_edge(Edge::new(1, 2, -1.0), policy),
+            Err(MarkovChainError::InvalidWeightError {
+                from: 1,
+                to: 2,
+                weight: -1.0
+            })
+        );
+        assert_eq!(mc.try_add_edge(Edge::new(1, 2, 1.0), policy), Ok(()));
+        assert_eq!(
+            mc.try_add_edge(Edge::new(1, 2, 1.0), policy),
+            Err(MarkovChainError::EdgeAlreadyExistsError { from: 1, to: 2 })
+        );
+    }
+
+    #[test]
+    fn test_try_add_edge_duplicate_policies() {
+        let mut mc = MarkovChain::new(Some(create_test_nodes()), Some(create_test_edges()));
+
+        mc.try_add_edge(Edge::new(1, 2, 0.5), DuplicateEdgePolicy::AddWeight)
+            .unwrap();
+        assert_eq!(mc.get_edge(1, 2).unwrap().weight, 1.5);
+
+        mc.try_add_edge(Edge::new(1, 2, 0.25), DuplicateEdgePolicy::Replace)
+            .unwrap();
+        assert_eq!(mc.get_node_edges(1).unwrap().len(), 2);
+        assert_eq!(mc.get_edge(1, 2).unwrap().weight, 0.25);
+        assert_eq!(mc.incoming_nodes(2), &[1]);
+    }
+
+    #[test]
+    fn test_try_add_edge_merges_actions() {
+        let mut mc = MarkovChain::new(Some(create_test_nodes()), Some(create_test_edges()));
+        mc.add_edge_actions(1, 2, &[Action::new(0, None), Action::new(1, None)]);
+
+        let edge = Edge {
+            actions: vec![Action::new(1, Some(Value::from(1)))],
+            ..Edge::new(1, 2, 0.5)
+        };
+        mc.try_add_edge(edge, DuplicateEdgePolicy::AddWeight)
+            .unwrap();
+        assert_eq!(
+            mc.get_edge_actions(1, 2).unwrap(),
+            &vec![Action::new(0, None), Action::new(1, Some(Value::from(1)))]
+        );
+
+        let edge = Edge {
+            actions: vec![Action::new(2, None)],
+            ..Edge::new(1, 2, 0.25)
+        };
+        mc.try_add_edge(edge, DuplicateEdgePolicy::Replace).unwrap();
+        assert_eq!(mc.get_edge(1, 2).unwrap().weight, 0.25);
+        assert_eq!(
+            mc.get_edge_actions(1, 2).unwrap(),
+            &vec![
+                Action::new(0, None),
+                Action::new(1, Some(Value::from(1))),
+                Action::new(2, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_try_remove_node_cascades() {
+        let mut mc = MarkovChain::new(Some(create_test_nodes()), Some(create_test_edges()));
+        mc.set_current_node(2).unwrap();
+
+        assert_eq!(mc.try_remove_node(2).unwrap().id, 2);
+        assert!(!mc.edge_exists(1, 2));
+        assert!(!mc.edge_exists(2, 3));
+        assert_eq!(mc.incoming_nodes(3), &[1]);
+        assert_eq!(mc.get_current_node(), None);
+        assert!(!mc.validate().iter().any(|diagnostic| diagnostic.is_error()));
+
+        assert_eq!(
+            mc.try_remove_node(2),
+            Err(MarkovChainError::NodeDoesNotExistError { node_id: 2 })
+        );
+        assert_eq!(
+            mc.try_remove_edge(1, 2),
+            Err(MarkovChainError::EdgeDoesNotExistError { from: 1, to: 2 })
+        );
+    }
+
+    #[test]
+    fn test_try_add_node_actions() {
+        let mut mc = MarkovChain::new(Some(create_test_nodes()), None);
+        let actions = [Action::new(1, None), Action::new(2, None)];
+
+        assert_eq!(
+            mc.try_add_node_actions(9, &actions),
+            Err(MarkovChainError::NodeDoesNotExistError { node_id: 9 })
+        );
+        assert_eq!(mc.try_add_node_actions(1, &actions), Ok(()));
+        assert_eq!(
+            mc.try_add_node_actions(1, &[Action::new(3, None), Action::new(2, None)]),
+            Err(MarkovChainError::ActionAlreadyExistsError {
+                node_id: 1,
+                action_id: 2
+            })
+        );
+        assert_eq!(mc.get_node_actions(1).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_try_add_nodes_edge_actions_and_terminal_nodes() {
+        let mut mc = MarkovChain::new(Some(create_test_nodes()), Some(create_test_edges()));
+
+        assert_eq!(
+            mc.try_add_nodes(&[Node::new(4, None), Node::new(1, None)]),
+            Err(MarkovChainError::NodeAlreadyExistsError { node_id: 1 })
+        );
+        assert_eq!(
+            mc.try_add_nodes(&[Node::named(4, "a", None), Node::named(5, "a", None)]),
+            Err(MarkovChainError::NameAlreadyExistsError {
+                name: "a".to_string()
+            })
+        );
+        assert!(!mc.node_exists(4));
+        assert_eq!(
+            mc.try_add_nodes(&[Node::new(4, None), Node::new(5, None)]),
+            Ok(())
+        );
+
+        assert_eq!(
+            mc.try_add_edge_actions(2, 1, &[Action::new(1, None)]),
+            Err(MarkovChainError::EdgeDoesNotExistError { from: 2, to: 1 })
+        );
+        assert_eq!(
+            mc.try_add_edge_actions(1, 2, &[Action::new(1, None), Action::new(1, None)]),
+            Err(MarkovChainError::EdgeActionAlreadyExistsError {
+                from: 1,
+                to: 2,
+                action_id: 1
+            })
+        );
+        assert!(mc.get_edge_actions(1, 2).unwrap().is_empty());
+        assert_eq!(
+            mc.try_add_edge_actions(1, 2, &[Action::new(1, None)]),
+            Ok(())
+        );
+
+        assert_eq!(
+            mc.try_add_terminal_node(9),
+            Err(MarkovChainError::NodeDoesNotExistError { node_id: 9 })
+        );
+        assert_eq!(mc.try_add_terminal_node(5), Ok(()));
+        assert!(mc.is_terminal_node(5));
+    }
+
+    #[test]
+    fn test_next_reports_failing_node() {
+        let mut mc = MarkovChain::new(Some(create_test_nodes()), Some(create_test_edges()));
+        mc.set_current_node(3).unwrap();
+        assert_eq!(
+            mc.next(),
+            Err(MarkovChainError::NodeHasNoEdgesError { node_id: 3 })
+        );
+
+        mc.add_edge(Edge::new(3, 2, 0.0));
+        assert_eq!(
+            mc.next(),
+            Err(MarkovChainError::TransitionFailedError { node_id: 3 })
+        );
+
+        mc.try_remove_node(1).unwrap();
+        mc.current_node = Some(1);
+        assert_eq!(
+            mc.next(),
+            Err(MarkovChainError::NodeDoesNotExistError { node_id: 1 })
+        );
+    }
+
+    #[test]
+    fn test_error_display_and_source() {
+        use std::error::Error;
+
+        let error = MarkovChain::from_json("{").unwrap_err();
+        assert!(error.source().is_some());
+        assert!(error
+            .to_string()
+            .starts_with("could not deserialize chain: "));
+
+        let error = MarkovChainError::EdgeDoesNotExistError { from: 1, to: 2 };
+        assert_eq!(error.to_string(), "edge 1 -> 2 does not exist");
+        assert!(error.source().is_none());
+    }
+
+    #[test]
+    fn test_generic_ids_and_payloads() {
+        #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+        struct Cue {
+            volume: u8,
+        }
+
+        let cue = GenericAction {
+            id: 0,
+            value: Cue { volume: 7 },
+            weight: 1.0,
+            trigger: Trigger::Enter,
+        };
+        let mut mc: GenericMarkovChain<String, Cue> = GenericMarkovChain::new(
+            Some(vec![
+                GenericNode::new("intro".to_string(), None),
+                GenericNode::new("verse".to_string(), Some(vec![cue.clone()])),
+            ]),
+            Some(vec![GenericEdge::new(
+                "intro".to_string(),
+                "verse".to_string(),
+                1.0,
+            )]),
+        );
+        mc.set_current_node("intro".to_string()).unwrap();
+
+        let step = mc.step().unwrap();
+        assert_eq!(step.to, "verse");
+        assert_eq!(step.actions, vec![cue]);
+        assert_eq!(
+            mc.next(),
+            Err(MarkovChainError::NodeHasNoEdgesError {
+                node_id: "verse".to_string()
+            })
+        );
+
+        let json = serde_json::to_string(&mc).unwrap();
+        let restored = GenericMarkovChain::<String, Cue>::from_json(&json).unwrap();
+        assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+        assert_eq!(restored.get_current_node().as_deref(), Some("verse"));
+    }
+
+    #[test]
+    fn test_step_and_act_selection() {
+        let actions = vec![
+            Action::weighted(10, None, 0.0),
+            Action::weighted(11, None, 2.0),
+            Action::weighted(12, None, 0.0),
+        ];
+        let mut mc = MarkovChain::new(
+            Some(vec![
+                Node::new(1, Some(actions.clone())),
+                Node::new(2, None),
+            ]),
+            Some(vec![Edge::new(2, 1, 1.0)]),
+        );
+        mc.set_seed(7);
+
+        let act_ids = |mc: &mut MarkovChain| -> Vec<u32> {
+            mc.set_current_node(2).unwrap();
+            mc.step_and_act()
+                .unwrap()
+                .iter()
+                .map(|action| action.id)
+                .collect()
+        };
+
+        assert_eq!(act_ids(&mut mc), vec![10, 11, 12]);
+
+        mc.set_action_selection(1, ActionSelection::Weighted)
+            .unwrap();
+        for _ in 0..10 {
+            assert_eq!(act_ids(&mut mc), vec![11]);
+        }
+
+        mc.set_action_selection(1, ActionSelection::Uniform)
+            .unwrap();
+        for _ in 0..10 {
+            assert_eq!(act_ids(&mut mc).len(), 1);
+        }
+
+        mc.set_action_selection(1, ActionSelection::RoundRobin)
+            .unwrap();
+        assert_eq!(act_ids(&mut mc), vec![10]);
+        assert_eq!(act_ids(&mut mc), vec![11]);
+
+        // The round-robin position survives a round trip.
+        let json = serde_json::to_string(&mc).unwrap();
+        let mut restored = MarkovChain::from_json(&json).unwrap();
+        assert_eq!(act_ids(&mut restored), vec![12]);
+        assert_eq!(act_ids(&mut restored), vec![10]);
+
+        mc.add_node(Node::new(1, Some(vec![Action::weighted(10, None, 0.0)])));
+        mc.set_action_selection(1, ActionSelection::Weighted)
+            .unwrap();
+        assert_eq!(act_ids(&mut mc), Vec::<u32>::new());
+        assert_eq!(
+            mc.set_action_selection(3, ActionSelection::All),
+            Err(MarkovChainError::NodeDoesNotExistError { node_id: 3 })
+        );
+    }
+
+    #[test]
+    fn test_edge_actions() {
+        let mut mc = create_cycle_chain();
+        mc.remove_edge(1, 3);
+        mc.add_node_actions(2, &[Action::new(20, None)]);
+        mc.add_edge_actions(1, 2, &[Action::new(5, Some(Value::from("crossfade")))]);
+        mc.add_edge_actions(1, 9, &[Action::new(6, None)]);
+        assert_eq!(
+            mc.get_edge_actions(1, 2),
+            Some(&vec![Action::new(5, Some(Value::from("crossfade")))])
+        );
+        assert_eq!(mc.get_edge_actions(2, 3), Some(&vec![]));
+        assert_eq!(mc.get_edge_actions(1, 9), None);
+
+        mc.set_current_node(1).unwrap();
+        let step = mc.step().unwrap();
+        assert_eq!(
+            step.edge_actions,
+            vec![Action::new(5, Some(Value::from("crossfade")))]
+        );
+        assert_eq!(step.actions, vec![Action::new(20, None)]);
+
+        let json = serde_json::to_string(&mc).unwrap();
+        assert!(json.contains(
+            r#"{"from":1,"to":2,"weight":1.0,"actions":[{"id":5,"value":"crossfade"}]}"#
+        ));
+        assert!(json.contains(r#"{"from":2,"to":3,"weight":1.0},"#));
+        let mut restored = MarkovChain::from_json(&json).unwrap();
+
+        restored.set_current_node(1).unwrap();
+        let fired: Vec<u32> = restored
+            .step_and_act()
+            .unwrap()
+            .iter()
+            .map(|action| action.id)
+            .collect();
+        assert_eq!(fired, vec![5, 20]);
+    }
+
+    #[test]
+    fn test_action_triggers_fire_in_order() {
+        let nodes = vec![
+            Node::new(
+                1,
+                Some(vec![
+                    Action::triggered(10, None, Trigger::Exit),
+                    Action::new(11, None),
+                    Action::triggered(12, None, Trigger::Stay),
+                ]),
+            ),
+            Node::new(
+                2,
+                Some(vec![
+                    Action::new(20, None),
+                    Action::triggered(21, None, Trigger::Exit),
+                    Action::new(22, None),
+                ]),
+            ),
+        ];
+        let mut mc = MarkovChain::new(Some(nodes), Some(vec![Edge::new(1, 2, 1.0)]));
+        mc.add_edge_actions(1, 2, &[Action::new(5, None)]);
+        mc.set_current_node(1).unwrap();
+
+        let step = mc.step().unwrap();
+        let fired: Vec<u32> = step.fired().map(|action| action.id).collect();
+        assert_eq!(fired, vec![10, 5, 20, 22]);
+
+        mc.set_current_node(1).unwrap();
+        let fired: Vec<u32> = mc
+            .step_and_act()
+            .unwrap()
+            .iter()
+            .map(|action| action.id)
+            .collect();
+        assert_eq!(fired, vec![10, 5, 20, 22]);
+
+        // A self-loop fires only the edge's actions and the stay actions.
+        mc.add_edge(Edge::new(2, 2, 1.0));
+        mc.add_node_actions(2, &[Action::triggered(23, None, Trigger::Stay)]);
+        let step = mc.step().unwrap();
+        let fired: Vec<u32> = step.fired().map(|action| action.id).collect();
+        assert_eq!(fired, vec![23]);
+
+        let json = serde_json::to_string(&mc).unwrap();
+        assert!(json.contains(r#"{"id":21,"value":null,"trigger":"exit"}"#));
+        assert!(json.contains(r#"{"id":20,"value":null}"#));
+    }
 }
@@ -1,6 +1,30 @@
+pub mod absorbing;
 pub mod action;
+pub mod alias;
+pub mod classes;
+pub mod distribution;
 pub mod edge;
+mod error;
+pub mod fit;
+pub mod higher_order;
+mod hitting;
+mod linalg;
+pub mod live;
+#[allow(clippy::module_inception)]
 mod markov_chain;
+pub mod names;
 pub mod node;
+pub mod online;
+pub mod schema;
+pub mod sink;
+mod stationary;
+#[cfg(test)]
+mod test_util;
+pub mod text;
+pub mod transition_matrix;
+pub mod validation;
+pub mod variable_order;
+pub mod walk;
 
-pub use markov_chain::MarkovChain;
+pub use error::MarkovChainError;
+pub use markov_chain::{DuplicateEdgePolicy, GenericMarkovChain, MarkovChain, SamplingMethod};
@@ -0,0 +1,225 @@
+use crate::markov_chain::{
+    edge::GenericEdge, node::GenericNode, GenericMarkovChain, MarkovChain, MarkovChainError,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::hash::Hash;
+
+/// A node as written in serialized edges: either its id or its name.
+///
+/// When `I` itself deserializes from a string, ids take precedence.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum NodeRef<I = u32> {
+    Id(I),
+    Name(String),
+}
+
+impl From<u32> for NodeRef {
+    fn from(node_id: u32) -> Self {
+        NodeRef::Id(node_id)
+    }
+}
+
+impl<I> From<&str> for NodeRef<I> {
+    fn from(name: &str) -> Self {
+        NodeRef::Name(name.to_string())
+    }
+}
+
+impl<I, V> GenericMarkovChain<I, V>
+where
+    I: Hash + Eq + Clone,
+    V: Clone,
+{
+    /// The id of the node called `name`.
+    pub fn node_id(&self, name: &str) -> Option<I> {
+        self.names().get(name).cloned()
+    }
+
+    /// The name of node `node_id`, if it has one.
+    pub fn node_name(&self, node_id: I) -> Option<&str> {
+        self.get_node(node_id).and_then(|node| node.name.as_deref())
+    }
+
+    /// Resolves a [`NodeRef`] to a node id. Ids are returned as-is, whether
+    /// or not the node exists.
+    pub fn resolve(&self, node: &NodeRef<I>) -> Result<I, MarkovChainError<I>> {
+        match node {
+            NodeRef::Id(node_id) => Ok(node_id.clone()),
+            NodeRef::Name(name) => self
+                .node_id(name)
+                .ok_or_else(|| MarkovChainError::UnknownNameError { name: name.clone() }),
+        }
+    }
+
+    pub fn get_node_by_name(&self, name: &str) -> Option<&GenericNode<I, V>> {
+        self.node_id(name)
+            .and_then(|node_id| self.get_node(node_id))
+    }
+
+    pub fn get_edge_by_name(&self, from: &str, to: &str) -> Option<&GenericEdge<I, V>> {
+        self.get_edge(self.node_id(from)?, self.node_id(to)?)
+    }
+
+    /// Adds an edge between two named nodes.
+    pub fn add_edge_by_name(
+        &mut self,
+        from: &str,
+        to: &str,
+        weight: f32,
+    ) -> Result<(), MarkovChainError<I>> {
+        let from = self.resolve(&from.into())?;
+        let to = self.resolve(&to.into())?;
+        self.add_edge(GenericEdge::new(from, to, weight));
+        Ok(())
+    }
+
+    pub fn set_current_node_by_name(&mut self, name: &str) -> Result<(), MarkovChainError<I>> {
+        let node_id = self.resolve(&name.into())?;
+        self.set_current_node(node_id)
+    }
+}
+
+impl MarkovChain {
+    /// The id of the node called `name`, adding a new node if there is none.
+    /// The new node gets the id after the highest one in use, or the lowest
+    /// free id once `u32::MAX` is taken.
+    pub fn intern_node(&mut self, name: &str) -> Result<u32, MarkovChainError> {
+        if let Some(node_id) = self.node_id(name) {
+            return Ok(node_id);
+        }
+        let node_id = match self.nodes().map(|node| node.id).max() {
+            None => 0,
+            Some(max) => match max.checked_add(1) {
+                Some(node_id) => node_id,
+                None => (0..u32::MAX)
+                    .find(|&node_id| !self.node_exists(node_id))
+                    .ok_or(MarkovChainError::NoFreeNodeIdError)?,
+            },
+        };
+        self.add_node(GenericNode::named(node_id, name, None));
+        Ok(node_id)
+    }
+
+    /// Serializes the chain like `serde_json::to_string`, but writes the
+    /// endpoints of each edge as node names wherever the node has one.
+    /// Deserializing accepts either form.
+    pub fn to_json_with_names(&self) -> Result<String, MarkovChainError> {
+        let mut value = serde_json::to_value(self).map_err(MarkovChainError::SerializationError)?;
+        if let Some(edges) = value["edges"].as_array_mut() {
+            for edge in edges {
+                for end in ["from", "to"] {
+                    let name = edge[end]
+                        .as_u64()
+                        .and_then(|node_id| self.node_name(node_id as u32));
+                    if let Some(name) = name {
+                        edge[end] = Value::String(name.to_string());
+                    }
+                }
+            }
+        }
+        serde_json::to_string(&value).map_err(MarkovChainError::SerializationError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markov_chain::{edge::Edge, node::Node, DuplicateEdgePolicy};
+
+    fn create_named_chain() -> MarkovChain {
+        let mut mc = MarkovChain::new(None, None);
+        mc.add_node(Node::named(1, "intro", None));
+        mc.add_node(Node::named(2, "verse", None));
+        mc.add_node(Node::new(3, None));
+        mc.add_edge_by_name("intro", "verse", 1.0).unwrap();
+        mc.add_edge(Edge::new(2, 3, 1.0));
+        mc
+    }
+
+    #[test]
+    fn test_lookups_by_name() {
+        let mut mc = create_named_chain();
+
+        assert_eq!(mc.node_id("verse"), Some(2));
+        assert_eq!(mc.get_node_by_name("intro").unwrap().id, 1);
+        assert_eq!(mc.get_edge_by_name("intro", "verse"), mc.get_edge(1, 2));
+        assert_eq!(
+            mc.add_edge_by_name("intro", "outro", 1.0),
+            Err(MarkovChainError::UnknownNameError {
+                name: "outro".to_string()
+            })
+        );
+
+        mc.set_current_node_by_name("verse").unwrap();
+        assert_eq!(mc.get_current_node(), Some(2));
+
+        assert_eq!(mc.intern_node("verse"), Ok(2));
+        assert_eq!(mc.intern_node("outro"), Ok(4));
+        assert_eq!(mc.node_name(4), Some("outro"));
+    }
+
+    #[test]
+    fn test_intern_node_after_max_id() {
+        let mut mc = create_named_chain();
+        mc.add_node(Node::new(u32::MAX, None));
+
+        assert_eq!(mc.intern_node("outro"), Ok(0));
+        assert_eq!(mc.intern_node("coda"), Ok(4));
+        assert_eq!(mc.node_id("intro"), Some(1));
+    }
+
+    #[test]
+    fn test_names_follow_node_changes() {
+        let mut mc = create_named_chain();
+
+        mc.add_node(Node::named(2, "chorus", None));
+        assert_eq!(mc.node_id("verse"), None);
+        assert_eq!(mc.node_id("chorus"), Some(2));
+
+        assert_eq!(
+            mc.try_add_node(Node::named(5, "chorus", None)),
+            Err(MarkovChainError::NameAlreadyExistsError {
+                name: "chorus".to_string()
+            })
+        );
+
+        mc.try_remove_node(2).unwrap();
+        assert_eq!(mc.node_id("chorus"), None);
+        assert!(mc
+            .try_add_edge(Edge::new(1, 3, 1.0), DuplicateEdgePolicy::Reject)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_add_node_takes_name_from_other_node() {
+        let mut mc = create_named_chain();
+        mc.add_node(Node::named(3, "verse", None));
+        assert_eq!(mc.node_id("verse"), Some(3));
+        assert_eq!(mc.node_name(2), None);
+
+        let loaded = MarkovChain::from_json(&mc.to_json_with_names().unwrap()).unwrap();
+        assert!(loaded.edge_exists(1, 2));
+        assert!(loaded.edge_exists(2, 3));
+        assert!(!loaded.edge_exists(3, 3));
+    }
+
+    #[test]
+    fn test_json_with_names_round_trip() {
+        let mc = create_named_chain();
+        let json = mc.to_json_with_names().unwrap();
+        assert!(json.contains(r#"{"from":"intro","to":"verse","weight":1.0}"#));
+        assert!(json.contains(r#"{"from":"verse","to":3,"weight":1.0}"#));
+
+        let loaded = MarkovChain::from_json(&json).unwrap();
+        assert_eq!(
+            serde_json::to_string(&loaded).unwrap(),
+            serde_json::to_string(&mc).unwrap()
+        );
+
+        let unknown =
+            r#"{"nodes":[],"edges":[{"from":"a","to":1,"weight":1.0}],"current_node":null}"#;
+        assert!(MarkovChain::from_json(unknown).is_err());
+    }
+}
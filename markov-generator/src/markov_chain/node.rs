@@ -1,22 +1,65 @@
-use crate::markov_chain::action::Action;
+use crate::markov_chain::action::GenericAction;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
+/// A node with ids of type `I` and action payloads of type `V`. Most code
+/// uses the [`Node`] alias.
 #[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
-pub struct Node {
-    pub id: u32,
-    pub actions: Vec<Action>,
+pub struct GenericNode<I, V> {
+    pub id: I,
+    /// An optional human-readable name, unique within a chain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub actions: Vec<GenericAction<V>>,
+    /// Which actions fire when a walk enters the node through
+    /// [`GenericMarkovChain::step_and_act`](crate::markov_chain::GenericMarkovChain::step_and_act).
+    #[serde(default, skip_serializing_if = "ActionSelection::is_all")]
+    pub selection: ActionSelection,
 }
 
-impl Node {
-    pub fn new(id: u32, actions: Option<Vec<Action>>) -> Node {
+pub type Node = GenericNode<u32, Value>;
+
+/// How a node picks which of its actions fire.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionSelection {
+    /// Every action fires, in order.
+    #[default]
+    All,
+    /// One action, picked uniformly at random.
+    Uniform,
+    /// One action, picked at random in proportion to its weight.
+    Weighted,
+    /// One action, cycling through them in order on each visit.
+    RoundRobin,
+}
+
+impl ActionSelection {
+    fn is_all(&self) -> bool {
+        *self == ActionSelection::All
+    }
+}
+
+impl<I, V> GenericNode<I, V> {
+    #[allow(clippy::manual_unwrap_or_default)]
+    pub fn new(id: I, actions: Option<Vec<GenericAction<V>>>) -> GenericNode<I, V> {
         let _actions = match actions {
             Some(actions) => actions,
             None => Vec::new(),
         };
 
-        Node {
+        GenericNode {
             id,
+            name: None,
             actions: _actions,
+            selection: ActionSelection::All,
+        }
+    }
+
+    pub fn named(id: I, name: &str, actions: Option<Vec<GenericAction<V>>>) -> GenericNode<I, V> {
+        GenericNode {
+            name: Some(name.to_string()),
+            ..GenericNode::new(id, actions)
         }
     }
 }
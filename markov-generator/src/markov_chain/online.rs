@@ -0,0 +1,306 @@
+use crate::markov_chain::{node::GenericNode, GenericMarkovChain, MarkovChainError};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+/// How [`GenericMarkovChain::observe`] forgets old observations.
+///
+/// Forgetting is tracked per source node: only observations leaving the same
+/// node age each other, so rarely visited nodes keep what they learned.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Forgetting {
+    /// Every observation counts forever.
+    #[default]
+    None,
+    /// Before each observation from a node, the counts already learned for
+    /// that node are multiplied by `factor`, which must be in `(0, 1]`.
+    ExponentialDecay { factor: f32 },
+    /// Only the last `size` observations from each node count.
+    SlidingWindow { size: usize },
+}
+
+/// The counts learned by [`GenericMarkovChain::observe`], kept apart from the edge
+/// weights that were there before so those act as priors that never decay.
+/// It is serialized with the chain, so forgetting carries on after a reload.
+#[derive(Clone, Debug)]
+pub(crate) struct OnlineLearner<I> {
+    forgetting: Forgetting,
+    learned: HashMap<I, HashMap<I, f32>>,
+    windows: HashMap<I, VecDeque<I>>,
+}
+
+impl<I> Default for OnlineLearner<I> {
+    fn default() -> Self {
+        OnlineLearner {
+            forgetting: Forgetting::default(),
+            learned: HashMap::new(),
+            windows: HashMap::new(),
+        }
+    }
+}
+
+/// The serialized form of an [`OnlineLearner`], with its maps flattened into
+/// sorted lists.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct LearnerData<I> {
+    forgetting: Forgetting,
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    learned: Vec<(I, I, f32)>,
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    windows: Vec<(I, Vec<I>)>,
+}
+
+impl<I: Ord> OnlineLearner<I> {
+    /// The state worth saving. Without forgetting nothing is learned, so there
+    /// is none.
+    pub(crate) fn to_data(&self) -> Option<LearnerData<&I>> {
+        if self.forgetting == Forgetting::None {
+            return None;
+        }
+
+        let mut learned: Vec<(&I, &I, f32)> = self
+            .learned
+            .iter()
+            .flat_map(|(from, counts)| counts.iter().map(move |(to, &count)| (from, to, count)))
+            .collect();
+        learned.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+        let mut windows: Vec<(&I, Vec<&I>)> = self
+            .windows
+            .iter()
+            .map(|(from, window)| (from, window.iter().collect()))
+            .collect();
+        windows.sort_by(|a, b| a.0.cmp(b.0));
+
+        Some(LearnerData {
+            forgetting: self.forgetting,
+            learned,
+            windows,
+        })
+    }
+}
+
+impl<I: Hash + Eq> From<LearnerData<I>> for OnlineLearner<I> {
+    fn from(data: LearnerData<I>) -> Self {
+        let mut learned: HashMap<I, HashMap<I, f32>> = HashMap::new();
+        for (from, to, count) in data.learned {
+            learned.entry(from).or_default().insert(to, count);
+        }
+
+        OnlineLearner {
+            forgetting: data.forgetting,
+            learned,
+            windows: data
+                .windows
+                .into_iter()
+                .map(|(from, window)| (from, window.into()))
+                .collect(),
+        }
+    }
+}
+
+impl<I, V> GenericMarkovChain<I, V>
+where
+    I: Hash + Eq + Clone,
+    V: Clone,
+{
+    /// Sets how old observations are forgotten. Learning restarts from the
+    /// current edge weights, which become the new priors.
+    ///
+    /// Fails with `InvalidParameterError` if a decay factor is outside
+    /// `(0, 1]` or a window is empty.
+    pub fn set_forgetting(&mut self, forgetting: Forgetting) -> Result<(), MarkovChainError<I>> {
+        match forgetting {
+            Forgetting::ExponentialDecay { factor } if !(factor > 0.0 && factor <= 1.0) => {
+                return Err(MarkovChainError::InvalidParameterError {
+                    name: "decay factor",
+                    value: factor as f64,
+                    expected: "in (0, 1]",
+                });
+            }
+            Forgetting::SlidingWindow { size: 0 } => {
+                return Err(MarkovChainError::InvalidParameterError {
+                    name: "window size",
+                    value: 0.0,
+                    expected: "positive",
+                });
+            }
+            _ => {}
+        }
+
+        *self.learner_mut() = OnlineLearner {
+            forgetting,
+            ..OnlineLearner::default()
+        };
+        Ok(())
+    }
+
+    pub fn get_forgetting(&self) -> Forgetting {
+        self.learner().forgetting
+    }
+
+    /// Records one observed transition by adding 1 to the weight of the edge
+    /// `from -> to`, applying the configured [`Forgetting`] to earlier
+    /// observations from `from`. Missing nodes and edges are created.
+    ///
+    /// Only edge weights change, so a walk in progress carries on from its
+    /// current node.
+    pub fn observe(&mut self, from: I, to: I) {
+        for node_id in [from.clone(), to.clone()] {
+            if !self.node_exists(node_id.clone()) {
+                self.add_node(GenericNode::new(node_id, None));
+            }
+        }
+
+        // Without forgetting nothing needs to be remembered but the weight.
+        if self.learner().forgetting == Forgetting::None {
+            *self.edge_weight_mut(from, to) += 1.0;
+            return;
+        }
+
+        let mut learner = std::mem::take(self.learner_mut());
+        let learned = learner.learned.entry(from.clone()).or_default();
+
+        match learner.forgetting {
+            Forgetting::None => unreachable!(),
+            Forgetting::ExponentialDecay { factor } => {
+                // Counts for edges removed since they were learned are dropped.
+                learned.retain(|target, count| {
+                    if !self.edge_exists(from.clone(), target.clone()) {
+                        return false;
+                    }
+                    let decayed = *count * factor;
+                    *self.edge_weight_mut(from.clone(), target.clone()) -= *count - decayed;
+                    *count = decayed;
+                    true
+                });
+            }
+            Forgetting::SlidingWindow { size } => {
+                let window = learner.windows.entry(from.clone()).or_default();
+                window.push_back(to.clone());
+                if window.len() > size {
+                    let oldest = window.pop_front().unwrap();
+                    *learned.entry(oldest.clone()).or_default() -= 1.0;
+                    if self.edge_exists(from.clone(), oldest.clone()) {
+                        *self.edge_weight_mut(from.clone(), oldest) -= 1.0;
+                    }
+                }
+            }
+        }
+
+        *learned.entry(to.clone()).or_default() += 1.0;
+        *self.edge_weight_mut(from, to) += 1.0;
+
+        *self.learner_mut() = learner;
+    }
+
+    /// Observes every consecutive pair in `sequence`.
+    pub fn observe_sequence(&mut self, sequence: &[I]) {
+        for pair in sequence.windows(2) {
+            self.observe(pair[0].clone(), pair[1].clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markov_chain::{edge::Edge, MarkovChain};
+
+    fn weight(mc: &MarkovChain, from: u32, to: u32) -> f32 {
+        mc.get_edge(from, to).map_or(0.0, |edge| edge.weight)
+    }
+
+    #[test]
+    fn test_observe_with_prior() {
+        let mut mc = MarkovChain::new(None, Some(vec![Edge::new(1, 2, 3.0)]));
+        mc.observe_sequence(&[1, 2, 1, 3]);
+
+        assert_eq!(weight(&mc, 1, 2), 4.0);
+        assert_eq!(weight(&mc, 2, 1), 1.0);
+        assert_eq!(weight(&mc, 1, 3), 1.0);
+        assert!(mc.node_exists(3));
+    }
+
+    #[test]
+    fn test_observe_with_exponential_decay() {
+        let mut mc = MarkovChain::new(None, Some(vec![Edge::new(1, 2, 3.0)]));
+        mc.set_forgetting(Forgetting::ExponentialDecay { factor: 0.5 })
+            .unwrap();
+
+        mc.observe(1, 2);
+        mc.observe(1, 3);
+        mc.observe(1, 3);
+
+        // The prior of 3 stays; the learned count of 1 halves twice.
+        assert_eq!(weight(&mc, 1, 2), 3.25);
+        assert_eq!(weight(&mc, 1, 3), 1.5);
+    }
+
+    #[test]
+    fn test_observe_with_sliding_window() {
+        let mut mc = MarkovChain::new(None, None);
+        mc.set_forgetting(Forgetting::SlidingWindow { size: 2 })
+            .unwrap();
+        mc.observe_sequence(&[1, 2, 1, 3, 1, 3, 1]);
+
+        assert_eq!(weight(&mc, 1, 2), 0.0);
+        assert_eq!(weight(&mc, 1, 3), 2.0);
+        assert_eq!(weight(&mc, 3, 1), 2.0);
+    }
+
+    #[test]
+    fn test_set_invalid_forgetting() {
+        let mut mc = MarkovChain::new(None, None);
+        mc.set_forgetting(Forgetting::SlidingWindow { size: 2 })
+            .unwrap();
+
+        assert!(mc
+            .set_forgetting(Forgetting::ExponentialDecay { factor: 1.5 })
+            .is_err());
+        assert!(mc
+            .set_forgetting(Forgetting::SlidingWindow { size: 0 })
+            .is_err());
+        assert_eq!(mc.get_forgetting(), Forgetting::SlidingWindow { size: 2 });
+    }
+
+    #[test]
+    fn test_forgetting_survives_round_trip() {
+        for forgetting in [
+            Forgetting::ExponentialDecay { factor: 0.5 },
+            Forgetting::SlidingWindow { size: 2 },
+        ] {
+            let mut mc = MarkovChain::new(None, Some(vec![Edge::new(1, 2, 3.0)]));
+            mc.set_forgetting(forgetting).unwrap();
+            mc.observe_sequence(&[1, 2, 1, 3, 1]);
+
+            let json = serde_json::to_string(&mc).unwrap();
+            let mut loaded = MarkovChain::from_json(&json).unwrap();
+            assert_eq!(loaded.get_forgetting(), forgetting);
+
+            mc.observe_sequence(&[1, 3, 1, 3]);
+            loaded.observe_sequence(&[1, 3, 1, 3]);
+            assert_eq!(
+                serde_json::to_string(&loaded).unwrap(),
+                serde_json::to_string(&mc).unwrap()
+            );
+            assert_eq!(weight(&loaded, 1, 2), weight(&mc, 1, 2));
+        }
+
+        let mut mc = MarkovChain::new(None, None);
+        mc.observe_sequence(&[1, 2, 1]);
+        assert!(mc.learner().learned.is_empty());
+        assert!(!serde_json::to_string(&mc).unwrap().contains("learner"));
+    }
+
+    #[test]
+    fn test_observe_keeps_walk_position() {
+        let mut mc = MarkovChain::fit([[1, 2, 1]]);
+        mc.set_current_node(2).unwrap();
+        mc.observe(2, 3);
+
+        assert_eq!(mc.get_current_node(), Some(2));
+        mc.next().unwrap();
+    }
+}
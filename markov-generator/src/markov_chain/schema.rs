@@ -0,0 +1,230 @@
+use crate::markov_chain::{MarkovChain, MarkovChainError};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// The expected shape of an action value, registered per action id with
+/// [`MarkovChain::set_action_schema`] and checked by
+/// [`MarkovChain::validate`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionSchema {
+    Any,
+    Null,
+    Bool,
+    Number,
+    /// A JSON integer literal, such as `3`. `3.0` does not match, since it
+    /// would not decode as an integer type either.
+    Integer,
+    String,
+    /// An array whose items all match the inner schema.
+    Array(Box<ActionSchema>),
+    /// An object with at least these fields. Other fields are allowed, and a
+    /// missing field is checked as `null`, so `OneOf([Null, ..])` makes a
+    /// field optional.
+    Object(BTreeMap<String, ActionSchema>),
+    /// A value matching any of these schemas.
+    OneOf(Vec<ActionSchema>),
+}
+
+impl ActionSchema {
+    pub fn matches(&self, value: &Value) -> bool {
+        self.mismatch(value).is_none()
+    }
+
+    /// Describes the first place where `value` does not match, or returns
+    /// `None` if it does. Places are written as JSON pointers.
+    pub fn mismatch(&self, value: &Value) -> Option<String> {
+        self.mismatch_at("", value)
+    }
+
+    fn mismatch_at(&self, pointer: &str, value: &Value) -> Option<String> {
+        let matches = match (self, value) {
+            (ActionSchema::Any, _)
+            | (ActionSchema::Null, Value::Null)
+            | (ActionSchema::Bool, Value::Bool(_))
+            | (ActionSchema::Number, Value::Number(_))
+            | (ActionSchema::String, Value::String(_)) => true,
+            (ActionSchema::Integer, Value::Number(number)) => number.is_i64() || number.is_u64(),
+            (ActionSchema::Array(items), Value::Array(values)) => {
+                return values.iter().enumerate().find_map(|(index, value)| {
+                    items.mismatch_at(&format!("{}/{}", pointer, index), value)
+                })
+            }
+            (ActionSchema::Object(fields), Value::Object(values)) => {
+                return fields.iter().find_map(|(name, schema)| {
+                    let value = values.get(name).unwrap_or(&Value::Null);
+                    schema.mismatch_at(&format!("{}/{}", pointer, name), value)
+                })
+            }
+            (ActionSchema::OneOf(schemas), _) => schemas.iter().any(|s| s.matches(value)),
+            _ => false,
+        };
+
+        if matches {
+            None
+        } else {
+            let pointer = if pointer.is_empty() { "/" } else { pointer };
+            Some(format!(
+                "{}: expected {}, found {}",
+                pointer,
+                self.name(),
+                value
+            ))
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            ActionSchema::Any => "anything".to_string(),
+            ActionSchema::Null => "null".to_string(),
+            ActionSchema::Bool => "a boolean".to_string(),
+            ActionSchema::Number => "a number".to_string(),
+            ActionSchema::Integer => "an integer".to_string(),
+            ActionSchema::String => "a string".to_string(),
+            ActionSchema::Array(_) => "an array".to_string(),
+            ActionSchema::Object(_) => "an object".to_string(),
+            ActionSchema::OneOf(schemas) => {
+                let names: Vec<String> = schemas.iter().map(ActionSchema::name).collect();
+                format!("one of ({})", names.join(", "))
+            }
+        }
+    }
+}
+
+impl MarkovChain {
+    /// Declares the shape that values of actions with id `action_id` must
+    /// have, replacing any earlier schema for that id.
+    pub fn set_action_schema(&mut self, action_id: u32, schema: ActionSchema) {
+        self.action_schemas_mut().insert(action_id, schema);
+    }
+
+    pub fn get_action_schema(&self, action_id: u32) -> Option<&ActionSchema> {
+        self.action_schemas().get(&action_id)
+    }
+
+    pub fn remove_action_schema(&mut self, action_id: u32) -> Option<ActionSchema> {
+        self.action_schemas_mut().remove(&action_id)
+    }
+
+    /// Decodes the value of every action on `node_id` as a `T`, in order.
+    pub fn decode_node_actions<T: DeserializeOwned>(
+        &self,
+        node_id: u32,
+    ) -> Result<Vec<T>, MarkovChainError> {
+        let actions = self
+            .get_node_actions(node_id)
+            .ok_or(MarkovChainError::NodeDoesNotExistError { node_id })?;
+
+        actions
+            .iter()
+            .map(|action| {
+                action
+                    .decode()
+                    .map_err(|error| MarkovChainError::ActionDecodeError {
+                        node_id,
+                        action_id: action.id,
+                        error,
+                    })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markov_chain::{action::Action, node::Node};
+    use serde_json::json;
+
+    fn cue_schema() -> ActionSchema {
+        ActionSchema::Object(BTreeMap::from([
+            ("volume".to_string(), ActionSchema::Integer),
+            (
+                "tags".to_string(),
+                ActionSchema::OneOf(vec![
+                    ActionSchema::Null,
+                    ActionSchema::Array(Box::new(ActionSchema::String)),
+                ]),
+            ),
+        ]))
+    }
+
+    #[test]
+    fn test_schema_mismatch() {
+        let schema = cue_schema();
+        assert!(schema.matches(&json!({ "volume": 3 })));
+        assert!(schema.matches(&json!({ "volume": 3, "tags": ["a"], "extra": true })));
+        assert_eq!(
+            schema.mismatch(&json!({ "volume": 3, "tags": ["a", 1] })),
+            Some("/tags: expected one of (null, an array), found [\"a\",1]".to_string())
+        );
+        assert_eq!(
+            schema.mismatch(&json!({ "volume": 0.5 })),
+            Some("/volume: expected an integer, found 0.5".to_string())
+        );
+        assert!(!ActionSchema::Integer.matches(&json!(3.0)));
+        assert_eq!(
+            ActionSchema::Array(Box::new(ActionSchema::Bool)).mismatch(&json!([true, null])),
+            Some("/1: expected a boolean, found null".to_string())
+        );
+        assert_eq!(
+            schema.mismatch(&json!("loud")),
+            Some("/: expected an object, found \"loud\"".to_string())
+        );
+    }
+
+    #[test]
+    fn test_decode_node_actions() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Cue {
+            volume: u8,
+        }
+
+        let mut mc = MarkovChain::new(
+            Some(vec![Node::new(
+                1,
+                Some(vec![
+                    Action::new(0, Some(json!({ "volume": 3 }))),
+                    Action::new(1, Some(json!({ "volume": 4 }))),
+                ]),
+            )]),
+            None,
+        );
+        assert_eq!(
+            mc.decode_node_actions::<Cue>(1).unwrap(),
+            vec![Cue { volume: 3 }, Cue { volume: 4 }]
+        );
+        assert_eq!(
+            mc.decode_node_actions::<Cue>(2),
+            Err(MarkovChainError::NodeDoesNotExistError { node_id: 2 })
+        );
+
+        mc.add_node_actions(1, &[Action::new(2, Some(json!({ "volume": "loud" })))]);
+        let error = mc.decode_node_actions::<Cue>(1).unwrap_err();
+        assert!(matches!(
+            error,
+            MarkovChainError::ActionDecodeError {
+                node_id: 1,
+                action_id: 2,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_action_schemas_round_trip() {
+        let mut mc = MarkovChain::new(Some(vec![Node::new(1, None)]), None);
+        mc.set_action_schema(7, cue_schema());
+
+        let json = serde_json::to_string(&mc).unwrap();
+        assert!(json.contains(r#""action_schemas":{"7":{"object":"#));
+        let restored = MarkovChain::from_json(&json).unwrap();
+        assert_eq!(restored.get_action_schema(7), Some(&cue_schema()));
+
+        assert_eq!(mc.remove_action_schema(7), Some(cue_schema()));
+        assert!(!serde_json::to_string(&mc)
+            .unwrap()
+            .contains("action_schemas"));
+    }
+}
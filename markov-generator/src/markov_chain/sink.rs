@@ -0,0 +1,402 @@
+use crate::markov_chain::{action::GenericAction, GenericMarkovChain, MarkovChainError};
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::hash::Hash;
+use std::io::{self, Stdout, Write};
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Something that executes the actions a running chain fires.
+pub trait ActionSink<I = u32, V = Value> {
+    /// Handles `action`, fired for `node_id`.
+    fn send(&mut self, node_id: &I, action: &GenericAction<V>) -> io::Result<()>;
+}
+
+/// The JSON form in which the built-in sinks write an action:
+/// `{"node_id":1,"action":{"id":0,"value":...}}`.
+#[derive(Debug, Serialize)]
+pub struct ActionEvent<'a, I, V> {
+    pub node_id: &'a I,
+    pub action: &'a GenericAction<V>,
+}
+
+/// Writes each action as one line of JSON and flushes after every line.
+#[derive(Debug)]
+pub struct JsonLinesSink<W> {
+    writer: W,
+}
+
+impl<W: Write> JsonLinesSink<W> {
+    pub fn new(writer: W) -> JsonLinesSink<W> {
+        JsonLinesSink { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl JsonLinesSink<Stdout> {
+    pub fn stdout() -> JsonLinesSink<Stdout> {
+        JsonLinesSink::new(io::stdout())
+    }
+}
+
+impl JsonLinesSink<File> {
+    /// Appends to the file at `path`, creating it if needed.
+    pub fn append(path: impl AsRef<Path>) -> io::Result<JsonLinesSink<File>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(JsonLinesSink::new(file))
+    }
+}
+
+#[cfg(unix)]
+impl JsonLinesSink<std::os::unix::net::UnixStream> {
+    /// Connects to the Unix domain socket listening at `path`.
+    pub fn unix_socket(
+        path: impl AsRef<Path>,
+    ) -> io::Result<JsonLinesSink<std::os::unix::net::UnixStream>> {
+        let stream = std::os::unix::net::UnixStream::connect(path)?;
+        Ok(JsonLinesSink::new(stream))
+    }
+}
+
+impl<I: Serialize, V: Serialize, W: Write> ActionSink<I, V> for JsonLinesSink<W> {
+    fn send(&mut self, node_id: &I, action: &GenericAction<V>) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, &ActionEvent { node_id, action })?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+}
+
+/// Sends each action as a JSON datagram, without a trailing newline.
+#[derive(Debug)]
+pub struct UdpSink {
+    socket: UdpSocket,
+}
+
+impl UdpSink {
+    /// Binds an ephemeral local port and sends to `target`.
+    pub fn new(target: impl ToSocketAddrs) -> io::Result<UdpSink> {
+        let target = target
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address to send to"))?;
+        let local = if target.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(target)?;
+        Ok(UdpSink { socket })
+    }
+}
+
+impl<I: Serialize, V: Serialize> ActionSink<I, V> for UdpSink {
+    fn send(&mut self, node_id: &I, action: &GenericAction<V>) -> io::Result<()> {
+        let datagram = serde_json::to_vec(&ActionEvent { node_id, action })?;
+        self.socket.send(&datagram)?;
+        Ok(())
+    }
+}
+
+/// How [`ProcessSink`] hands the action value to the process.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProcessInput {
+    /// Write the value as one line of JSON to stdin.
+    #[default]
+    Stdin,
+    /// Pass the value as JSON in a final argument.
+    Arg,
+}
+
+/// Runs a program once per action and waits for it to exit.
+///
+/// The process also sees the node and action ids in the `MARKOV_NODE_ID` and
+/// `MARKOV_ACTION_ID` environment variables. A non-zero exit status is
+/// reported as an error.
+#[derive(Clone, Debug)]
+pub struct ProcessSink {
+    program: String,
+    args: Vec<String>,
+    input: ProcessInput,
+}
+
+impl ProcessSink {
+    pub fn new(program: &str, args: &[&str], input: ProcessInput) -> ProcessSink {
+        ProcessSink {
+            program: program.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            input,
+        }
+    }
+}
+
+impl<I: Serialize, V: Serialize> ActionSink<I, V> for ProcessSink {
+    fn send(&mut self, node_id: &I, action: &GenericAction<V>) -> io::Result<()> {
+        let value = serde_json::to_string(&action.value)?;
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .env("MARKOV_NODE_ID", serde_json::to_string(node_id)?)
+            .env("MARKOV_ACTION_ID", action.id.to_string());
+
+        let status = match self.input {
+            ProcessInput::Stdin => {
+                let mut child = command.stdin(Stdio::piped()).spawn()?;
+                // Write from another thread, so a value larger than the pipe
+                // buffer cannot block us while the process is not reading.
+                let writer = child
+                    .stdin
+                    .take()
+                    .map(|mut stdin| thread::spawn(move || writeln!(stdin, "{}", value)));
+                let status = child.wait()?;
+                let written = writer.map_or(Ok(()), |writer| {
+                    writer
+                        .join()
+                        .unwrap_or_else(|_| Err(io::Error::other("stdin writer panicked")))
+                });
+                // A process may exit without reading its input; the exit
+                // status decides whether that is a failure.
+                match written {
+                    Err(error) if error.kind() != io::ErrorKind::BrokenPipe => return Err(error),
+                    _ => status,
+                }
+            }
+            ProcessInput::Arg => command.arg(value).status()?,
+        };
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!(
+                "{} exited with {}",
+                self.program, status
+            )))
+        }
+    }
+}
+
+/// An action that one of the sinks of an [`ActionDispatcher`] failed to
+/// handle.
+#[derive(Debug)]
+pub struct SinkFailure<I = u32> {
+    /// The position of the sink, in the order the sinks were added.
+    pub sink: usize,
+    pub node_id: I,
+    pub action_id: u32,
+    pub error: io::Error,
+}
+
+/// Fans the actions a chain fires out to several sinks.
+///
+/// Every action goes to every sink in the order they were added. A sink that
+/// fails does not stop the others, or later actions; failures are returned
+/// to the caller instead.
+pub struct ActionDispatcher<I = u32, V = Value> {
+    sinks: Vec<Box<dyn ActionSink<I, V>>>,
+}
+
+impl<I, V> Default for ActionDispatcher<I, V> {
+    fn default() -> Self {
+        ActionDispatcher { sinks: Vec::new() }
+    }
+}
+
+impl<I, V> ActionDispatcher<I, V>
+where
+    I: Hash + Eq + Clone,
+    V: Clone,
+{
+    pub fn new() -> Self {
+        ActionDispatcher::default()
+    }
+
+    pub fn add_sink(&mut self, sink: impl ActionSink<I, V> + 'static) {
+        self.sinks.push(Box::new(sink));
+    }
+
+    pub fn len(&self) -> usize {
+        self.sinks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    /// Sends one action to every sink.
+    pub fn dispatch(&mut self, node_id: &I, action: &GenericAction<V>) -> Vec<SinkFailure<I>> {
+        self.sinks
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(sink, handler)| {
+                handler
+                    .send(node_id, action)
+                    .err()
+                    .map(|error| SinkFailure {
+                        sink,
+                        node_id: node_id.clone(),
+                        action_id: action.id,
+                        error,
+                    })
+            })
+            .collect()
+    }
+
+    /// Advances `chain` like [`GenericMarkovChain::step_and_act`] and
+    /// dispatches every action that fires, in firing order. Edge actions are
+    /// sent with the id of the node that was left.
+    pub fn step(
+        &mut self,
+        chain: &mut GenericMarkovChain<I, V>,
+    ) -> Result<Vec<SinkFailure<I>>, MarkovChainError<I>> {
+        let events = chain.fire()?;
+        Ok(events
+            .iter()
+            .flat_map(|(node_id, action)| self.dispatch(node_id, action))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markov_chain::{
+        action::{Action, Trigger},
+        edge::Edge,
+        node::Node,
+        MarkovChain,
+    };
+    use std::cell::RefCell;
+    use std::fs;
+    use std::rc::Rc;
+
+    /// Records what it receives, and fails on actions with id 0.
+    struct Recorder(Rc<RefCell<Vec<(u32, u32)>>>);
+
+    impl ActionSink for Recorder {
+        fn send(&mut self, node_id: &u32, action: &Action) -> io::Result<()> {
+            if action.id == 0 {
+                return Err(io::Error::other("refused"));
+            }
+            self.0.borrow_mut().push((*node_id, action.id));
+            Ok(())
+        }
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("markov-sink-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_json_lines_and_file_sinks() {
+        let mut sink = JsonLinesSink::new(Vec::new());
+        sink.send(&1, &Action::new(2, Some(Value::from("go"))))
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(sink.into_inner()).unwrap(),
+            "{\"node_id\":1,\"action\":{\"id\":2,\"value\":\"go\"}}\n"
+        );
+
+        let path = temp_path("append");
+        let _ = fs::remove_file(&path);
+        for node_id in [1, 2] {
+            let mut sink = JsonLinesSink::append(&path).unwrap();
+            sink.send(&node_id, &Action::new(0, None)).unwrap();
+        }
+        let lines = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(lines.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_socket_sinks() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut sink = UdpSink::new(receiver.local_addr().unwrap()).unwrap();
+        sink.send(&3, &Action::new(4, None)).unwrap();
+        let mut buffer = [0; 128];
+        let len = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(
+            &buffer[..len],
+            b"{\"node_id\":3,\"action\":{\"id\":4,\"value\":null}}"
+        );
+
+        #[cfg(unix)]
+        {
+            use std::io::{BufRead, BufReader};
+            use std::os::unix::net::UnixListener;
+
+            let path = temp_path("socket");
+            let _ = fs::remove_file(&path);
+            let listener = UnixListener::bind(&path).unwrap();
+            let mut sink = JsonLinesSink::unix_socket(&path).unwrap();
+            sink.send(&5, &Action::new(6, None)).unwrap();
+
+            let (stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(stream).read_line(&mut line).unwrap();
+            fs::remove_file(&path).unwrap();
+            assert_eq!(
+                line,
+                "{\"node_id\":5,\"action\":{\"id\":6,\"value\":null}}\n"
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_process_sink() {
+        let action = Action::new(7, Some(Value::from(42)));
+
+        let script = "read value; test \"$value\" = 42 && test \"$MARKOV_ACTION_ID\" = 7";
+        let mut sink = ProcessSink::new("sh", &["-c", script], ProcessInput::Stdin);
+        assert!(sink.send(&1, &action).is_ok());
+
+        let large = Action::new(8, Some(Value::from("x".repeat(1 << 20))));
+        let mut sink = ProcessSink::new("true", &[], ProcessInput::Stdin);
+        assert!(sink.send(&1, &large).is_ok());
+        let mut sink = ProcessSink::new("false", &[], ProcessInput::Stdin);
+        assert!(sink.send(&1, &large).is_err());
+
+        let script = "test \"$1\" = 42 && test \"$MARKOV_NODE_ID\" = 1";
+        let mut sink = ProcessSink::new("sh", &["-c", script, "sh"], ProcessInput::Arg);
+        assert!(sink.send(&1, &action).is_ok());
+        assert!(sink.send(&2, &action).is_err());
+    }
+
+    #[test]
+    fn test_dispatcher_keeps_going_after_failures() {
+        let nodes = vec![
+            Node::new(1, Some(vec![Action::triggered(0, None, Trigger::Exit)])),
+            Node::new(2, Some(vec![Action::new(20, None)])),
+        ];
+        let mut mc = MarkovChain::new(Some(nodes), Some(vec![Edge::new(1, 2, 1.0)]));
+        mc.add_edge_actions(1, 2, &[Action::new(5, None)]);
+        mc.set_current_node(1).unwrap();
+
+        let first = Rc::new(RefCell::new(Vec::new()));
+        let second = Rc::new(RefCell::new(Vec::new()));
+        let mut dispatcher = ActionDispatcher::new();
+        dispatcher.add_sink(Recorder(first.clone()));
+        dispatcher.add_sink(Recorder(second.clone()));
+        assert_eq!(dispatcher.len(), 2);
+
+        let failures = dispatcher.step(&mut mc).unwrap();
+        let failed: Vec<(usize, u32, u32)> = failures
+            .iter()
+            .map(|failure| (failure.sink, failure.node_id, failure.action_id))
+            .collect();
+        assert_eq!(failed, vec![(0, 1, 0), (1, 1, 0)]);
+        assert_eq!(*first.borrow(), vec![(1, 5), (2, 20)]);
+        assert_eq!(*second.borrow(), vec![(1, 5), (2, 20)]);
+
+        assert_eq!(
+            dispatcher.step(&mut mc).unwrap_err(),
+            MarkovChainError::NodeHasNoEdgesError { node_id: 2 }
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use crate::markov_chain::{
+    classes::{period, recurrent_classes},
+    linalg::solve,
+    transition_matrix::TransitionMatrix,
+    MarkovChain, MarkovChainError,
+};
+use std::collections::HashMap;
+
+impl MarkovChain {
+    /// Computes the unique stationary distribution by solving `πP = π` with
+    /// `Σπ = 1` directly. See [`TransitionMatrix`] for how edge weights become
+    /// probabilities.
+    ///
+    /// Fails with `ReducibleChainError` if the chain has more than one
+    /// recurrent class, since the stationary distribution is then not unique;
+    /// use [`MarkovChain::stationary_distributions`] instead. Periodic chains
+    /// are fine here. The solve is dense, so it costs O(n³) in the number of
+    /// nodes.
+    pub fn stationary_distribution(&self) -> Result<HashMap<u32, f64>, MarkovChainError> {
+        let matrix = self.transition_matrix()?;
+        check_single_recurrent_class(&matrix)?;
+
+        let rows: Vec<usize> = (0..matrix.len()).collect();
+        let pi = solve_stationary(&matrix, &rows)?;
+        Ok(matrix.to_map(&pi))
+    }
+
+    /// Computes the stationary distribution by repeatedly applying the
+    /// transition matrix to the uniform distribution until successive
+    /// distributions differ by less than `tolerance` in L1 norm.
+    ///
+    /// Besides the reducible case of [`MarkovChain::stationary_distribution`],
+    /// this fails with `PeriodicChainError` when the recurrent class is
+    /// periodic, because the iteration would oscillate instead of converging,
+    /// and with `NotConvergedError` after `max_iterations` steps.
+    pub fn stationary_distribution_power_iteration(
+        &self,
+        tolerance: f64,
+        max_iterations: usize,
+    ) -> Result<HashMap<u32, f64>, MarkovChainError> {
+        let matrix = self.transition_matrix()?;
+        let class = check_single_recurrent_class(&matrix)?;
+        if let Some(class) = class {
+            let period = period(&matrix, &class);
+            if period > 1 {
+                return Err(MarkovChainError::PeriodicChainError { period });
+            }
+        }
+
+        let n = matrix.len();
+        let mut pi = vec![1.0 / n as f64; n];
+        for _ in 0..max_iterations {
+            let next = matrix.step(&pi);
+            let delta: f64 = next.iter().zip(&pi).map(|(a, b)| (a - b).abs()).sum();
+            pi = next;
+            if delta < tolerance {
+                return Ok(matrix.to_map(&pi));
+            }
+        }
+
+        Err(MarkovChainError::NotConvergedError {
+            iterations: max_iterations,
+        })
+    }
+
+    /// Computes one stationary distribution per recurrent class, each
+    /// supported on its class only. Every stationary distribution of the
+    /// chain is a convex combination of these.
+    pub fn stationary_distributions(&self) -> Result<Vec<HashMap<u32, f64>>, MarkovChainError> {
+        let matrix = self.transition_matrix()?;
+
+        recurrent_classes(&matrix)
+            .into_iter()
+            .map(|class| {
+                let pi = solve_stationary(&matrix, &class)?;
+                let mut full = vec![0.0; matrix.len()];
+                for (&i, p) in class.iter().zip(pi) {
+                    full[i] = p;
+                }
+                Ok(matrix.to_map(&full))
+            })
+            .collect()
+    }
+}
+
+/// Returns the only recurrent class, or `None` for an empty chain.
+fn check_single_recurrent_class(
+    matrix: &TransitionMatrix,
+) -> Result<Option<Vec<usize>>, MarkovChainError> {
+    let mut classes = recurrent_classes(matrix);
+    if classes.len() > 1 {
+        let recurrent_classes = classes
+            .iter()
+            .map(|class| class.iter().map(|&i| matrix.node_ids()[i]).collect())
+            .collect();
+        return Err(MarkovChainError::ReducibleChainError { recurrent_classes });
+    }
+    Ok(classes.pop())
+}
+
+/// Solves for the stationary distribution of the chain restricted to `rows`,
+/// which must contain at most one recurrent class and every row reachable
+/// from it. Transient rows come out as zero.
+fn solve_stationary(
+    matrix: &TransitionMatrix,
+    rows: &[usize],
+) -> Result<Vec<f64>, MarkovChainError> {
+    let n = rows.len();
+    if n == 0 {
+        return Ok(Vec::new());
+    }
+
+    let position: HashMap<usize, usize> = rows.iter().enumerate().map(|(k, &i)| (i, k)).collect();
+
+    // (Pᵀ - I) π = 0, with the last equation swapped for Σπ = 1.
+    let mut a = vec![vec![0.0; n]; n];
+    for (k, &i) in rows.iter().enumerate() {
+        a[k][k] -= 1.0;
+        for &(j, p) in matrix.row(i) {
+            if let Some(&l) = position.get(&j) {
+                a[l][k] += p;
+            }
+        }
+    }
+    a[n - 1] = vec![1.0; n];
+    let mut b = vec![0.0; n];
+    b[n - 1] = 1.0;
+
+    solve(a, b).ok_or(MarkovChainError::SingularMatrixError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markov_chain::test_util::chain;
+
+    fn assert_close(actual: &HashMap<u32, f64>, expected: &[(u32, f64)]) {
+        assert_eq!(actual.len(), expected.len());
+        for &(node_id, p) in expected {
+            assert!((actual[&node_id] - p).abs() < 1e-6, "{:?}", actual);
+        }
+    }
+
+    #[test]
+    fn test_stationary_distribution() {
+        // Two-state chain with P(1 -> 2) = 0.25 and P(2 -> 1) = 0.5.
+        let mc = chain(
+            &[1, 2],
+            &[(1, 1, 3.0), (1, 2, 1.0), (2, 1, 1.0), (2, 2, 1.0)],
+        );
+        let expected = [(1, 2.0 / 3.0), (2, 1.0 / 3.0)];
+
+        assert_close(&mc.stationary_distribution().unwrap(), &expected);
+        assert_close(
+            &mc.stationary_distribution_power_iteration(1e-12, 10_000)
+                .unwrap(),
+            &expected,
+        );
+    }
+
+    #[test]
+    fn test_stationary_distribution_with_transient_node() {
+        let mc = chain(
+            &[1, 2, 3],
+            &[(1, 2, 1.0), (2, 3, 1.0), (3, 2, 1.0), (3, 3, 1.0)],
+        );
+        assert_close(
+            &mc.stationary_distribution().unwrap(),
+            &[(1, 0.0), (2, 1.0 / 3.0), (3, 2.0 / 3.0)],
+        );
+    }
+
+    #[test]
+    fn test_periodic_chain() {
+        let mc = chain(&[1, 2], &[(1, 2, 1.0), (2, 1, 1.0)]);
+        assert_close(
+            &mc.stationary_distribution().unwrap(),
+            &[(1, 0.5), (2, 0.5)],
+        );
+        assert_eq!(
+            mc.stationary_distribution_power_iteration(1e-9, 1000),
+            Err(MarkovChainError::PeriodicChainError { period: 2 })
+        );
+    }
+
+    #[test]
+    fn test_reducible_chain() {
+        // Node 3 has no edges, so it is absorbing alongside the 1 <-> 2 loop.
+        let mc = chain(
+            &[1, 2, 3, 4],
+            &[(1, 2, 1.0), (2, 1, 1.0), (4, 1, 1.0), (4, 3, 1.0)],
+        );
+        assert_eq!(
+            mc.stationary_distribution(),
+            Err(MarkovChainError::ReducibleChainError {
+                recurrent_classes: vec![vec![1, 2], vec![3]],
+            })
+        );
+
+        let distributions = mc.stationary_distributions().unwrap();
+        assert_eq!(distributions.len(), 2);
+        assert_close(&distributions[0], &[(1, 0.5), (2, 0.5), (3, 0.0), (4, 0.0)]);
+        assert_close(&distributions[1], &[(1, 0.0), (2, 0.0), (3, 1.0), (4, 0.0)]);
+    }
+}
@@ -0,0 +1,14 @@
+use crate::markov_chain::{edge::Edge, node::Node, MarkovChain};
+
+/// A chain with action-less nodes `node_ids` and `(from, to, weight)` edges.
+pub(crate) fn chain(node_ids: &[u32], edges: &[(u32, u32, f32)]) -> MarkovChain {
+    MarkovChain::new(
+        Some(node_ids.iter().map(|&id| Node::new(id, None)).collect()),
+        Some(
+            edges
+                .iter()
+                .map(|&(from, to, weight)| Edge::new(from, to, weight))
+                .collect(),
+        ),
+    )
+}
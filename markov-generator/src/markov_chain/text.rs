@@ -0,0 +1,366 @@
+use crate::markov_chain::{action::Action, MarkovChain, MarkovChainError};
+use rand::Rng;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// The id of the action holding each token node's text.
+pub const TOKEN_ACTION_ID: u32 = 0;
+/// The node every sentence starts from.
+pub const START_NODE_ID: u32 = 0;
+/// The terminal node every sentence ends in.
+pub const END_NODE_ID: u32 = 1;
+
+const SENTENCE_END: [&str; 3] = [".", "!", "?"];
+const NO_SPACE_BEFORE: [&str; 10] = [".", ",", "!", "?", ";", ":", "%", ")", "]", "}"];
+const NO_SPACE_AFTER: [&str; 3] = ["(", "[", "{"];
+const QUOTES: [&str; 2] = ["\"", "'"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenLevel {
+    /// Words and punctuation marks. Apostrophes and hyphens inside a word
+    /// stay part of it.
+    Word,
+    /// Single characters, including whitespace.
+    Character,
+}
+
+/// Splits `text` into tokens.
+pub fn tokenize(text: &str, level: TokenLevel) -> Vec<String> {
+    match level {
+        TokenLevel::Character => text.chars().map(String::from).collect(),
+        TokenLevel::Word => {
+            let mut tokens = Vec::new();
+            let mut word = String::new();
+            let mut chars = text.chars().peekable();
+
+            while let Some(c) = chars.next() {
+                let joins_word = (c == '\'' || c == '-')
+                    && !word.is_empty()
+                    && chars.peek().is_some_and(|next| next.is_alphanumeric());
+                if c.is_alphanumeric() || joins_word {
+                    word.push(c);
+                    continue;
+                }
+
+                if !word.is_empty() {
+                    tokens.push(std::mem::take(&mut word));
+                }
+                if !c.is_whitespace() {
+                    tokens.push(c.to_string());
+                }
+            }
+            if !word.is_empty() {
+                tokens.push(word);
+            }
+
+            tokens
+        }
+    }
+}
+
+/// Joins tokens back into text. Word tokens are separated by spaces, except
+/// before closing punctuation and after opening brackets. Quote marks
+/// alternate between opening, attached to the next token, and closing,
+/// attached to the previous one.
+pub fn detokenize<S: AsRef<str>>(tokens: &[S], level: TokenLevel) -> String {
+    let mut text = String::new();
+    let mut open_quotes: Vec<&str> = Vec::new();
+    let mut attach_next = false;
+    for (i, token) in tokens.iter().enumerate() {
+        let token = token.as_ref();
+        if level == TokenLevel::Word {
+            let (space_before, attaches) = if QUOTES.contains(&token) {
+                match open_quotes.iter().rposition(|&quote| quote == token) {
+                    Some(position) => {
+                        open_quotes.truncate(position);
+                        (false, false)
+                    }
+                    None => {
+                        open_quotes.push(token);
+                        (true, true)
+                    }
+                }
+            } else {
+                (
+                    !NO_SPACE_BEFORE.contains(&token),
+                    NO_SPACE_AFTER.contains(&token),
+                )
+            };
+            if i > 0 && space_before && !attach_next {
+                text.push(' ');
+            }
+            attach_next = attaches;
+        }
+        text.push_str(token);
+    }
+    text
+}
+
+/// Options for [`TextChain::generate_with_rng`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GenerateOptions {
+    /// The end of the sentence is not allowed before this many tokens.
+    pub min_length: usize,
+    /// Generation stops after this many tokens even without an end.
+    pub max_length: usize,
+    /// Tokens the sentence starts with; generation continues from the last.
+    pub prefix: Vec<String>,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        GenerateOptions {
+            min_length: 1,
+            max_length: 50,
+            prefix: Vec::new(),
+        }
+    }
+}
+
+/// A [`MarkovChain`] over the tokens of a corpus.
+///
+/// Every distinct token gets a node whose [`TOKEN_ACTION_ID`] action holds
+/// the token as a JSON string. Each sentence of the corpus becomes a sequence
+/// from [`START_NODE_ID`] to the terminal [`END_NODE_ID`]. At word level a
+/// sentence ends after `.`, `!` or `?`; at character level every non-empty
+/// line is a sentence.
+#[derive(Debug)]
+pub struct TextChain {
+    level: TokenLevel,
+    chain: MarkovChain,
+    tokens: Vec<String>,
+    ids: HashMap<String, u32>,
+}
+
+impl TextChain {
+    pub fn build(corpus: &str, level: TokenLevel) -> TextChain {
+        let sentences: Vec<Vec<String>> = match level {
+            TokenLevel::Word => {
+                let mut sentences = vec![Vec::new()];
+                for token in tokenize(corpus, level) {
+                    let ends_sentence = SENTENCE_END.contains(&token.as_str());
+                    sentences.last_mut().unwrap().push(token);
+                    if ends_sentence {
+                        sentences.push(Vec::new());
+                    }
+                }
+                sentences
+            }
+            TokenLevel::Character => corpus.lines().map(|line| tokenize(line, level)).collect(),
+        };
+
+        let mut text_chain = TextChain {
+            level,
+            chain: MarkovChain::new(None, None),
+            tokens: Vec::new(),
+            ids: HashMap::new(),
+        };
+
+        let sequences: Vec<Vec<u32>> = sentences
+            .into_iter()
+            .filter(|sentence| !sentence.is_empty())
+            .map(|sentence| {
+                let mut sequence = vec![START_NODE_ID];
+                sequence.extend(sentence.into_iter().map(|token| text_chain.intern(token)));
+                sequence.push(END_NODE_ID);
+                sequence
+            })
+            .collect();
+
+        let mut chain = MarkovChain::fit(sequences);
+        for (i, token) in text_chain.tokens.iter().enumerate() {
+            let action = Action::new(TOKEN_ACTION_ID, Some(Value::String(token.clone())));
+            chain.add_node_actions(i as u32 + 2, &[action]);
+        }
+        chain.add_terminal_node(END_NODE_ID);
+        text_chain.chain = chain;
+
+        text_chain
+    }
+
+    pub fn level(&self) -> TokenLevel {
+        self.level
+    }
+
+    pub fn chain(&self) -> &MarkovChain {
+        &self.chain
+    }
+
+    /// The node id of `token`, if it occurs in the corpus.
+    pub fn token_id(&self, token: &str) -> Option<u32> {
+        self.ids.get(token).copied()
+    }
+
+    /// The token of node `node_id`, or `None` for the sentinels.
+    pub fn token(&self, node_id: u32) -> Option<&str> {
+        let index = node_id.checked_sub(2)? as usize;
+        self.tokens.get(index).map(String::as_str)
+    }
+
+    pub fn generate(&self, options: &GenerateOptions) -> Result<String, MarkovChainError> {
+        self.generate_with_rng(options, &mut rand::thread_rng())
+    }
+
+    /// Generates a sentence, drawing from `rng`.
+    ///
+    /// Fails with `UnknownTokenError` if the prefix contains a token that is
+    /// not in the corpus, and with `NodeHasNoEdgesError` if the sentence can
+    /// only end before `min_length` tokens.
+    pub fn generate_with_rng<R: Rng + ?Sized>(
+        &self,
+        options: &GenerateOptions,
+        rng: &mut R,
+    ) -> Result<String, MarkovChainError> {
+        let mut tokens: Vec<&str> = Vec::new();
+        let mut node_id = START_NODE_ID;
+        for token in options.prefix.iter() {
+            node_id = self
+                .token_id(token)
+                .ok_or_else(|| MarkovChainError::UnknownTokenError {
+                    token: token.clone(),
+                })?;
+            tokens.push(token);
+        }
+
+        while tokens.len() < options.max_length {
+            let edges: Vec<_> = self
+                .chain
+                .outgoing_edges(node_id)
+                .iter()
+                .filter(|edge| edge.to != END_NODE_ID || tokens.len() >= options.min_length)
+                .cloned()
+                .collect();
+            if edges.is_empty() {
+                if self.chain.edge_exists(node_id, END_NODE_ID) {
+                    return Err(MarkovChainError::NodeHasNoEdgesError { node_id });
+                }
+                break;
+            }
+
+            let index = MarkovChain::sample_cumulative(&edges, rng)
+                .ok_or(MarkovChainError::TransitionFailedError { node_id })?;
+            node_id = edges[index].to;
+            match self.token(node_id) {
+                Some(token) => tokens.push(token),
+                None => break,
+            }
+        }
+
+        Ok(detokenize(&tokens, self.level))
+    }
+
+    fn intern(&mut self, token: String) -> u32 {
+        if let Some(&id) = self.ids.get(&token) {
+            return id;
+        }
+        let id = self.tokens.len() as u32 + 2;
+        self.tokens.push(token.clone());
+        self.ids.insert(token, id);
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_tokenize_and_detokenize() {
+        let text = "Don't stop (yet), well-known cat!";
+        let tokens = tokenize(text, TokenLevel::Word);
+        assert_eq!(
+            tokens,
+            vec![
+                "Don't",
+                "stop",
+                "(",
+                "yet",
+                ")",
+                ",",
+                "well-known",
+                "cat",
+                "!"
+            ]
+        );
+        assert_eq!(detokenize(&tokens, TokenLevel::Word), text);
+
+        for text in [
+            "He said \"hi\" to me.",
+            "'quoted' and \"nested 'inner' quotes\"",
+        ] {
+            let tokens = tokenize(text, TokenLevel::Word);
+            assert_eq!(detokenize(&tokens, TokenLevel::Word), text);
+        }
+
+        let chars = tokenize("a b", TokenLevel::Character);
+        assert_eq!(chars, vec!["a", " ", "b"]);
+        assert_eq!(detokenize(&chars, TokenLevel::Character), "a b");
+    }
+
+    #[test]
+    fn test_build_interns_tokens() {
+        let text_chain = TextChain::build("the cat sat. the dog sat.", TokenLevel::Word);
+        let the = text_chain.token_id("the").unwrap();
+        let chain = text_chain.chain();
+
+        assert_eq!(text_chain.token(the), Some("the"));
+        assert_eq!(
+            chain.get_node_action(the, TOKEN_ACTION_ID).unwrap().value,
+            Value::String("the".to_string())
+        );
+        assert_eq!(chain.get_edge(START_NODE_ID, the).unwrap().weight, 1.0);
+        assert!(chain.edge_exists(text_chain.token_id(".").unwrap(), END_NODE_ID));
+        assert!(chain.is_terminal_node(END_NODE_ID));
+    }
+
+    #[test]
+    fn test_generate() {
+        let text_chain = TextChain::build("the cat sat. the dog ran.", TokenLevel::Word);
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+
+        for _ in 0..20 {
+            let sentence = text_chain
+                .generate_with_rng(&GenerateOptions::default(), &mut rng)
+                .unwrap();
+            assert!(sentence.starts_with("the ") && sentence.ends_with('.'));
+        }
+
+        let options = GenerateOptions {
+            prefix: vec!["dog".to_string()],
+            ..GenerateOptions::default()
+        };
+        assert_eq!(
+            text_chain.generate_with_rng(&options, &mut rng).unwrap(),
+            "dog ran."
+        );
+
+        let options = GenerateOptions {
+            max_length: 2,
+            ..GenerateOptions::default()
+        };
+        let sentence = text_chain.generate_with_rng(&options, &mut rng).unwrap();
+        assert_eq!(tokenize(&sentence, TokenLevel::Word).len(), 2);
+
+        let options = GenerateOptions {
+            min_length: 5,
+            ..GenerateOptions::default()
+        };
+        assert!(matches!(
+            text_chain.generate_with_rng(&options, &mut rng),
+            Err(MarkovChainError::NodeHasNoEdgesError { .. })
+        ));
+
+        let options = GenerateOptions {
+            prefix: vec!["bird".to_string()],
+            ..GenerateOptions::default()
+        };
+        assert_eq!(
+            text_chain.generate_with_rng(&options, &mut rng),
+            Err(MarkovChainError::UnknownTokenError {
+                token: "bird".to_string()
+            })
+        );
+    }
+}
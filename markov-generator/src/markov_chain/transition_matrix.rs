@@ -0,0 +1,159 @@
+use crate::markov_chain::{MarkovChain, MarkovChainError};
+use std::collections::HashMap;
+
+/// The row-stochastic transition matrix of a [`MarkovChain`].
+///
+/// Rows and columns follow the node ids in ascending order. Each row holds
+/// the outgoing edge weights of a node divided by their total, with parallel
+/// edges summed and zero weights left out. A node without outgoing edges is
+/// treated as absorbing, i.e. it gets a self-loop with probability 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransitionMatrix {
+    node_ids: Vec<u32>,
+    index: HashMap<u32, usize>,
+    rows: Vec<Vec<(usize, f64)>>,
+}
+
+impl TransitionMatrix {
+    /// Fails if an edge touches a missing node, has an invalid weight, or if
+    /// a node's outgoing weights sum to zero.
+    pub fn new(mc: &MarkovChain) -> Result<TransitionMatrix, MarkovChainError> {
+        let mut node_ids: Vec<u32> = mc.nodes().map(|node| node.id).collect();
+        node_ids.sort();
+        let index: HashMap<u32, usize> = node_ids
+            .iter()
+            .enumerate()
+            .map(|(i, &node_id)| (node_id, i))
+            .collect();
+
+        let mut rows = Vec::with_capacity(node_ids.len());
+        for (i, &node_id) in node_ids.iter().enumerate() {
+            let edges = mc.outgoing_edges(node_id);
+            if edges.is_empty() {
+                rows.push(vec![(i, 1.0)]);
+                continue;
+            }
+
+            let mut weights: HashMap<usize, f64> = HashMap::new();
+            for edge in edges {
+                if !edge.weight.is_finite() || edge.weight < 0.0 {
+                    return Err(MarkovChainError::InvalidWeightError {
+                        from: edge.from,
+                        to: edge.to,
+                        weight: edge.weight,
+                    });
+                }
+                let j = *index
+                    .get(&edge.to)
+                    .ok_or(MarkovChainError::NodeDoesNotExistError { node_id: edge.to })?;
+                if edge.weight > 0.0 {
+                    *weights.entry(j).or_default() += edge.weight as f64;
+                }
+            }
+
+            let total: f64 = weights.values().sum();
+            if total <= 0.0 {
+                return Err(MarkovChainError::TransitionFailedError { node_id });
+            }
+
+            let mut row: Vec<(usize, f64)> = weights
+                .into_iter()
+                .map(|(j, weight)| (j, weight / total))
+                .collect();
+            row.sort_by_key(|&(j, _)| j);
+            rows.push(row);
+        }
+
+        Ok(TransitionMatrix {
+            node_ids,
+            index,
+            rows,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.node_ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.node_ids.is_empty()
+    }
+
+    /// The node id of every row, in order.
+    pub fn node_ids(&self) -> &[u32] {
+        &self.node_ids
+    }
+
+    pub fn index_of(&self, node_id: u32) -> Option<usize> {
+        self.index.get(&node_id).copied()
+    }
+
+    /// The non-zero entries of row `i` as `(column, probability)` pairs.
+    pub fn row(&self, i: usize) -> &[(usize, f64)] {
+        &self.rows[i]
+    }
+
+    /// The probability of moving from `from` to `to` in one step.
+    pub fn probability(&self, from: u32, to: u32) -> f64 {
+        match (self.index_of(from), self.index_of(to)) {
+            (Some(i), Some(j)) => self.rows[i]
+                .iter()
+                .find(|&&(column, _)| column == j)
+                .map_or(0.0, |&(_, p)| p),
+            _ => 0.0,
+        }
+    }
+
+    pub fn to_dense(&self) -> Vec<Vec<f64>> {
+        let mut dense = vec![vec![0.0; self.len()]; self.len()];
+        for (i, row) in self.rows.iter().enumerate() {
+            for &(j, p) in row {
+                dense[i][j] = p;
+            }
+        }
+        dense
+    }
+
+    /// Advances a distribution over the rows by one step, i.e. returns `pP`.
+    pub fn step(&self, distribution: &[f64]) -> Vec<f64> {
+        let mut next = vec![0.0; self.len()];
+        for (i, row) in self.rows.iter().enumerate() {
+            if distribution[i] == 0.0 {
+                continue;
+            }
+            for &(j, p) in row {
+                next[j] += distribution[i] * p;
+            }
+        }
+        next
+    }
+
+    /// Replaces row `i` with a self-loop of probability 1.
+    pub(crate) fn set_absorbing(&mut self, i: usize) {
+        self.rows[i] = vec![(i, 1.0)];
+    }
+
+    /// Makes every terminal node of `mc` absorbing, since walks stop there.
+    pub(crate) fn absorb_terminal_nodes(&mut self, mc: &MarkovChain) {
+        for i in 0..self.len() {
+            if mc.is_terminal_node(self.node_ids[i]) {
+                self.set_absorbing(i);
+            }
+        }
+    }
+
+    /// Turns a vector indexed like the rows into a map keyed by node id.
+    pub(crate) fn to_map(&self, values: &[f64]) -> HashMap<u32, f64> {
+        self.node_ids
+            .iter()
+            .copied()
+            .zip(values.iter().copied())
+            .collect()
+    }
+}
+
+impl MarkovChain {
+    pub fn transition_matrix(&self) -> Result<TransitionMatrix, MarkovChainError> {
+        TransitionMatrix::new(self)
+    }
+}
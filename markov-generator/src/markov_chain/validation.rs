@@ -0,0 +1,517 @@
+use crate::markov_chain::{
+    action::Action, markov_chain::ChainData, node::Node, MarkovChain, MarkovChainError,
+};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Where in the chain a [`Diagnostic`] applies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Location {
+    Chain,
+    Node(u32),
+    Edge { from: u32, to: u32 },
+    Action { node_id: u32, action_id: u32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// An edge or the current node refers to a node id that does not exist.
+    MissingNode,
+    /// Two nodes share an id. Only detectable in serialized input.
+    DuplicateNode,
+    /// Two nodes share a name, so edges written by name are ambiguous.
+    DuplicateName,
+    /// More than one edge connects the same pair of nodes.
+    DuplicateEdge,
+    /// An edge weight is negative, NaN or infinite.
+    InvalidWeight,
+    /// An edge weight is zero, so the edge is never taken.
+    ZeroWeight,
+    /// A node has outgoing edges but none of them can be taken.
+    UnsampleableNode,
+    /// Two actions on the same node or edge share an id.
+    DuplicateAction,
+    /// An action value does not match the schema registered for its id.
+    SchemaMismatch,
+    /// A node cannot be reached from the start node.
+    UnreachableNode,
+    /// A non-terminal node has no outgoing edges, so walks get stuck there.
+    SinkNode,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub kind: DiagnosticKind,
+    pub location: Location,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, kind: DiagnosticKind, location: Location, message: String) -> Self {
+        Diagnostic {
+            severity,
+            kind,
+            location,
+            message,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Chain => write!(f, "chain"),
+            Location::Node(node_id) => write!(f, "node {}", node_id),
+            Location::Edge { from, to } => write!(f, "edge {} -> {}", from, to),
+            Location::Action { node_id, action_id } => {
+                write!(f, "action {} on node {}", action_id, node_id)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {}: {}", severity, self.location, self.message)
+    }
+}
+
+impl MarkovChain {
+    /// Checks the chain for structural problems.
+    ///
+    /// Reachability is checked from the current node, and skipped if there is
+    /// none; use [`MarkovChain::validate_from`] to pick the start explicitly.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = self.validate_structure();
+        if let Some(start) = self.get_current_node().filter(|&id| self.node_exists(id)) {
+            diagnostics.extend(self.validate_reachability(start));
+        }
+        diagnostics
+    }
+
+    /// Like [`MarkovChain::validate`], but checks reachability from `start`.
+    pub fn validate_from(&self, start: u32) -> Vec<Diagnostic> {
+        let mut diagnostics = self.validate_structure();
+        diagnostics.extend(self.validate_reachability(start));
+        diagnostics
+    }
+
+    /// Deserializes a chain and refuses it if validation reports any errors.
+    pub fn from_json_validated(json: &str) -> Result<MarkovChain, MarkovChainError> {
+        let data: ChainData<u32, Value> =
+            serde_json::from_str(json).map_err(MarkovChainError::DeserializationError)?;
+
+        let mut diagnostics = Vec::new();
+        let mut seen = HashSet::new();
+        for node in data.nodes.iter() {
+            if !seen.insert(node.id) {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    DiagnosticKind::DuplicateNode,
+                    Location::Node(node.id),
+                    format!("node id {} is used more than once", node.id),
+                ));
+            }
+        }
+        diagnostics.extend(duplicate_names(data.nodes.iter()));
+
+        let mc = MarkovChain::try_from(data)?;
+        diagnostics.extend(mc.validate());
+
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            Err(MarkovChainError::InvalidChainError(diagnostics))
+        } else {
+            Ok(mc)
+        }
+    }
+
+    fn sorted_node_ids(&self) -> Vec<u32> {
+        let mut node_ids: Vec<u32> = self.nodes().map(|node| node.id).collect();
+        node_ids.sort();
+        node_ids
+    }
+
+    fn validate_structure(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        if let Some(node_id) = self.get_current_node() {
+            if !self.node_exists(node_id) {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    DiagnosticKind::MissingNode,
+                    Location::Chain,
+                    format!("current node {} does not exist", node_id),
+                ));
+            }
+        }
+
+        let mut nodes: Vec<&Node> = self.nodes().collect();
+        nodes.sort_by_key(|node| node.id);
+        diagnostics.extend(duplicate_names(nodes.into_iter()));
+
+        let mut sources: Vec<u32> = self.edges().map(|edge| edge.from).collect();
+        sources.sort();
+        sources.dedup();
+        for from in sources {
+            self.validate_outgoing_edges(from, &mut diagnostics);
+        }
+
+        for node_id in self.sorted_node_ids() {
+            let actions = self
+                .get_node_actions(node_id)
+                .map_or(&[][..], Vec::as_slice);
+            self.validate_actions(
+                actions,
+                |action_id| Location::Action { node_id, action_id },
+                &mut diagnostics,
+            );
+
+            if self.outgoing_edges(node_id).is_empty() && !self.is_terminal_node(node_id) {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Warning,
+                    DiagnosticKind::SinkNode,
+                    Location::Node(node_id),
+                    "node has no outgoing edges and is not terminal".to_string(),
+                ));
+            }
+        }
+
+        diagnostics
+    }
+
+    /// Checks one node's or edge's actions for duplicate ids and values that
+    /// do not match their schema.
+    fn validate_actions(
+        &self,
+        actions: &[Action],
+        location: impl Fn(u32) -> Location,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let mut action_ids = HashSet::new();
+        for action in actions {
+            if !action_ids.insert(action.id) {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    DiagnosticKind::DuplicateAction,
+                    location(action.id),
+                    format!("action id {} is used more than once", action.id),
+                ));
+            }
+
+            let mismatch = self
+                .get_action_schema(action.id)
+                .and_then(|schema| schema.mismatch(&action.value));
+            if let Some(mismatch) = mismatch {
+                let location = location(action.id);
+                let subject = match location {
+                    Location::Action { .. } => "value".to_string(),
+                    _ => format!("value of action {}", action.id),
+                };
+                diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    DiagnosticKind::SchemaMismatch,
+                    location,
+                    format!("{} does not match its schema at {}", subject, mismatch),
+                ));
+            }
+        }
+    }
+
+    fn validate_outgoing_edges(&self, from: u32, diagnostics: &mut Vec<Diagnostic>) {
+        let mut edge_counts: HashMap<u32, usize> = HashMap::new();
+        let mut total_weight = 0.0;
+        let mut all_valid = true;
+
+        for edge in self.outgoing_edges(from) {
+            let location = Location::Edge { from, to: edge.to };
+
+            for (end, node_id) in [("source", edge.from), ("target", edge.to)] {
+                if !self.node_exists(node_id) {
+                    diagnostics.push(Diagnostic::new(
+                        Severity::Error,
+                        DiagnosticKind::MissingNode,
+                        location,
+                        format!("{} node {} does not exist", end, node_id),
+                    ));
+                }
+            }
+
+            if !edge.weight.is_finite() || edge.weight < 0.0 {
+                all_valid = false;
+                diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    DiagnosticKind::InvalidWeight,
+                    location,
+                    format!(
+                        "weight {} is not a finite, non-negative number",
+                        edge.weight
+                    ),
+                ));
+            } else if edge.weight == 0.0 {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Warning,
+                    DiagnosticKind::ZeroWeight,
+                    location,
+                    "weight is zero, so the edge is never taken".to_string(),
+                ));
+            } else {
+                total_weight += edge.weight;
+            }
+
+            self.validate_actions(&edge.actions, |_| location, diagnostics);
+
+            *edge_counts.entry(edge.to).or_default() += 1;
+        }
+
+        let mut duplicates: Vec<(u32, usize)> = edge_counts
+            .into_iter()
+            .filter(|&(_, count)| count > 1)
+            .collect();
+        duplicates.sort();
+        for (to, count) in duplicates {
+            diagnostics.push(Diagnostic::new(
+                Severity::Warning,
+                DiagnosticKind::DuplicateEdge,
+                Location::Edge { from, to },
+                format!("{} edges connect the same nodes", count),
+            ));
+        }
+
+        if all_valid && total_weight <= 0.0 {
+            diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                DiagnosticKind::UnsampleableNode,
+                Location::Node(from),
+                "outgoing edge weights sum to zero".to_string(),
+            ));
+        }
+    }
+
+    fn validate_reachability(&self, start: u32) -> Vec<Diagnostic> {
+        let mut reached = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(node_id) = queue.pop_front() {
+            for edge in self.outgoing_edges(node_id) {
+                if reached.insert(edge.to) {
+                    queue.push_back(edge.to);
+                }
+            }
+        }
+
+        self.sorted_node_ids()
+            .into_iter()
+            .filter(|node_id| !reached.contains(node_id))
+            .map(|node_id| {
+                Diagnostic::new(
+                    Severity::Warning,
+                    DiagnosticKind::UnreachableNode,
+                    Location::Node(node_id),
+                    format!("node cannot be reached from node {}", start),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Reports every node whose name was already taken by an earlier node.
+fn duplicate_names<'a>(nodes: impl Iterator<Item = &'a Node>) -> Vec<Diagnostic> {
+    let mut seen = HashSet::new();
+    nodes
+        .filter_map(|node| node.name.as_ref().map(|name| (node.id, name)))
+        .filter(|&(_, name)| !seen.insert(name))
+        .map(|(node_id, name)| {
+            Diagnostic::new(
+                Severity::Error,
+                DiagnosticKind::DuplicateName,
+                Location::Node(node_id),
+                format!("name {:?} is used more than once", name),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markov_chain::{edge::Edge, schema::ActionSchema};
+
+    fn kinds(diagnostics: &[Diagnostic]) -> Vec<(DiagnosticKind, Location)> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.kind, diagnostic.location))
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_reports_problems() {
+        let nodes = vec![
+            Node::new(1, Some(vec![Action::new(5, None), Action::new(5, None)])),
+            Node::new(2, None),
+            Node::new(3, None),
+            Node::new(4, None),
+        ];
+        let edges = vec![
+            Edge::new(1, 2, 1.0),
+            Edge::new(1, 9, f32::NAN),
+            Edge::new(2, 1, 0.0),
+            Edge::new(2, 3, 1.0),
+            Edge::new(4, 1, 1.0),
+        ];
+        let mut mc = MarkovChain::new(Some(nodes), Some(edges));
+        mc.set_current_node(1).unwrap();
+
+        assert_eq!(
+            kinds(&mc.validate()),
+            vec![
+                (
+                    DiagnosticKind::MissingNode,
+                    Location::Edge { from: 1, to: 9 }
+                ),
+                (
+                    DiagnosticKind::InvalidWeight,
+                    Location::Edge { from: 1, to: 9 }
+                ),
+                (
+                    DiagnosticKind::ZeroWeight,
+                    Location::Edge { from: 2, to: 1 }
+                ),
+                (
+                    DiagnosticKind::DuplicateAction,
+                    Location::Action {
+                        node_id: 1,
+                        action_id: 5
+                    }
+                ),
+                (DiagnosticKind::SinkNode, Location::Node(3)),
+                (DiagnosticKind::UnreachableNode, Location::Node(4)),
+            ]
+        );
+
+        mc.add_terminal_node(3);
+        mc.remove_node(4);
+        assert!(kinds(&mc.validate()).contains(&(
+            DiagnosticKind::MissingNode,
+            Location::Edge { from: 4, to: 1 }
+        )));
+    }
+
+    #[test]
+    fn test_from_json_validated() {
+        let valid = r#"{"nodes":[{"id":1,"actions":[]},{"id":2,"actions":[]}],
+            "edges":[{"from":1,"to":2,"weight":1.0},{"from":2,"to":1,"weight":1.0}],
+            "current_node":1}"#;
+        assert!(MarkovChain::from_json_validated(valid).is_ok());
+
+        let duplicate = r#"{"nodes":[{"id":1,"actions":[]},{"id":1,"actions":[]}],
+            "edges":[{"from":1,"to":1,"weight":1.0}],"current_node":null}"#;
+        match MarkovChain::from_json_validated(duplicate) {
+            Err(MarkovChainError::InvalidChainError(diagnostics)) => assert_eq!(
+                kinds(&diagnostics),
+                vec![(DiagnosticKind::DuplicateNode, Location::Node(1))]
+            ),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let duplicate_name = r#"{"nodes":[{"id":1,"name":"a","actions":[]},
+            {"id":2,"name":"a","actions":[]}],
+            "edges":[{"from":1,"to":2,"weight":1.0},{"from":2,"to":1,"weight":1.0}],
+            "current_node":1}"#;
+        match MarkovChain::from_json_validated(duplicate_name) {
+            Err(MarkovChainError::InvalidChainError(diagnostics)) => assert_eq!(
+                kinds(&diagnostics),
+                vec![(DiagnosticKind::DuplicateName, Location::Node(2))]
+            ),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        assert!(matches!(
+            MarkovChain::from_json_validated("{"),
+            Err(MarkovChainError::DeserializationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_action_schemas() {
+        let nodes = vec![Node::new(
+            1,
+            Some(vec![
+                Action::new(0, Some(Value::from(2))),
+                Action::new(1, Some(Value::from("two"))),
+            ]),
+        )];
+        let mut mc = MarkovChain::new(Some(nodes), Some(vec![Edge::new(1, 1, 1.0)]));
+        mc.set_action_schema(0, ActionSchema::Integer);
+        mc.set_action_schema(1, ActionSchema::Integer);
+
+        let diagnostics = mc.validate();
+        assert_eq!(
+            kinds(&diagnostics),
+            vec![(
+                DiagnosticKind::SchemaMismatch,
+                Location::Action {
+                    node_id: 1,
+                    action_id: 1
+                }
+            )]
+        );
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "error: action 1 on node 1: value does not match its schema at /: expected an integer, found \"two\""
+        );
+
+        mc.set_action_schema(1, ActionSchema::Any);
+        assert!(mc.validate().is_empty());
+    }
+
+    #[test]
+    fn test_validate_edge_actions() {
+        let nodes = vec![Node::new(1, None), Node::new(2, None)];
+        let edges = vec![Edge::new(1, 2, 1.0), Edge::new(2, 1, 1.0)];
+        let mut mc = MarkovChain::new(Some(nodes), Some(edges));
+        mc.add_edge_actions(
+            1,
+            2,
+            &[
+                Action::new(0, Some(Value::from(2))),
+                Action::new(0, Some(Value::from(3))),
+            ],
+        );
+        mc.add_edge_actions(2, 1, &[Action::new(0, Some(Value::from("two")))]);
+        mc.set_action_schema(0, ActionSchema::Integer);
+
+        let diagnostics = mc.validate();
+        assert_eq!(
+            kinds(&diagnostics),
+            vec![
+                (
+                    DiagnosticKind::DuplicateAction,
+                    Location::Edge { from: 1, to: 2 }
+                ),
+                (
+                    DiagnosticKind::SchemaMismatch,
+                    Location::Edge { from: 2, to: 1 }
+                ),
+            ]
+        );
+        assert_eq!(
+            diagnostics[1].to_string(),
+            "error: edge 2 -> 1: value of action 0 does not match its schema at /: expected an integer, found \"two\""
+        );
+    }
+}
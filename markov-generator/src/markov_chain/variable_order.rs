@@ -0,0 +1,411 @@
+use crate::markov_chain::{edge::Edge, node::Node, MarkovChain, MarkovChainError};
+use rand::Rng;
+use std::collections::{BTreeSet, HashMap};
+
+/// How a [`VariableOrderChain`] combines contexts of different lengths.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backoff {
+    /// Katz-style backoff: use the longest context seen at least `min_count`
+    /// times, subtract `discount` from each of its counts, and share the
+    /// freed mass among unseen successors in proportion to the next shorter
+    /// context.
+    Katz { discount: f32, min_count: u32 },
+    /// Interpolated Kneser-Ney: always mix each context with the next shorter
+    /// one, weighting shorter contexts by how many distinct contexts a
+    /// successor follows rather than how often it occurs.
+    InterpolatedKneserNey { discount: f32 },
+}
+
+/// A variable-length Markov model over node ids, in the style of a
+/// prediction suffix tree.
+///
+/// Transition counts are kept for every context of up to `max_order` ids.
+/// When predicting, a long context that was never seen, or seen too rarely,
+/// backs off to its shorter suffixes as configured by [`Backoff`]. Walking
+/// works like [`MarkovChain::next`], with the walk's history as context.
+#[derive(Clone, Debug)]
+pub struct VariableOrderChain {
+    max_order: usize,
+    backoff: Backoff,
+    nodes: HashMap<u32, Node>,
+    counts: HashMap<Vec<u32>, HashMap<u32, f64>>,
+    continuation_counts: HashMap<Vec<u32>, HashMap<u32, f64>>,
+    history: Vec<u32>,
+}
+
+impl VariableOrderChain {
+    /// Fits a model to observed sequences of node ids.
+    ///
+    /// Fails with `InvalidParameterError` if the discount is outside `[0, 1]`.
+    pub fn fit<I, S>(
+        max_order: usize,
+        backoff: Backoff,
+        sequences: I,
+    ) -> Result<VariableOrderChain, MarkovChainError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<[u32]>,
+    {
+        let discount = match backoff {
+            Backoff::Katz { discount, .. } => discount,
+            Backoff::InterpolatedKneserNey { discount } => discount,
+        };
+        if !(0.0..=1.0).contains(&discount) {
+            return Err(MarkovChainError::InvalidParameterError {
+                name: "discount",
+                value: discount as f64,
+                expected: "in [0, 1]",
+            });
+        }
+
+        let mut node_ids = BTreeSet::new();
+        let mut counts: HashMap<Vec<u32>, HashMap<u32, f64>> = HashMap::new();
+        for sequence in sequences {
+            let sequence = sequence.as_ref();
+            node_ids.extend(sequence.iter().copied());
+            for (i, &next) in sequence.iter().enumerate() {
+                for length in 0..=max_order.min(i) {
+                    *counts
+                        .entry(sequence[i - length..i].to_vec())
+                        .or_default()
+                        .entry(next)
+                        .or_default() += 1.0;
+                }
+            }
+        }
+
+        // The continuation count of `next` after `context` is the number of
+        // distinct ids seen right before `context` when it led to `next`.
+        let mut continuation_counts: HashMap<Vec<u32>, HashMap<u32, f64>> = HashMap::new();
+        for (context, row) in counts.iter().filter(|(context, _)| !context.is_empty()) {
+            let suffix = continuation_counts
+                .entry(context[1..].to_vec())
+                .or_default();
+            for &next in row.keys() {
+                *suffix.entry(next).or_default() += 1.0;
+            }
+        }
+
+        Ok(VariableOrderChain {
+            max_order,
+            backoff,
+            nodes: node_ids
+                .into_iter()
+                .map(|node_id| (node_id, Node::new(node_id, None)))
+                .collect(),
+            counts,
+            continuation_counts,
+            history: Vec::new(),
+        })
+    }
+
+    pub fn max_order(&self) -> usize {
+        self.max_order
+    }
+
+    pub fn backoff(&self) -> Backoff {
+        self.backoff
+    }
+
+    /// Replaces the node with the same id, e.g. to attach actions.
+    pub fn add_node(&mut self, node: Node) {
+        self.nodes.insert(node.id, node);
+    }
+
+    pub fn get_node(&self, node_id: u32) -> Option<&Node> {
+        self.nodes.get(&node_id)
+    }
+
+    /// How often `context` was followed by anything in the training data.
+    pub fn context_count(&self, context: &[u32]) -> f64 {
+        self.counts
+            .get(context)
+            .map_or(0.0, |row| row.values().sum())
+    }
+
+    /// The predicted probability of every node following `context`, as edges
+    /// from the context's last id in ascending `to` order. Only the last
+    /// `max_order` ids of `context` are used.
+    pub fn transition_edges(&self, context: &[u32]) -> Vec<Edge> {
+        let from = context.last().copied().unwrap_or_default();
+        let context = &context[context.len().saturating_sub(self.max_order)..];
+
+        let mut distribution: Vec<(u32, f64)> = match self.backoff {
+            Backoff::Katz {
+                discount,
+                min_count,
+            } => self.katz(context, discount as f64, min_count as f64),
+            Backoff::InterpolatedKneserNey { discount } => {
+                self.kneser_ney(context, discount as f64, true)
+            }
+        }
+        .into_iter()
+        .filter(|&(_, p)| p > 0.0)
+        .collect();
+        distribution.sort_by_key(|&(to, _)| to);
+
+        distribution
+            .into_iter()
+            .map(|(to, p)| Edge::new(from, to, p as f32))
+            .collect()
+    }
+
+    /// Starts a new walk at `node_id`, forgetting the previous history.
+    pub fn set_current_node(&mut self, node_id: u32) -> Result<(), MarkovChainError> {
+        self.set_history(&[node_id])
+    }
+
+    /// Starts a new walk with `history` as the context, most recent id last.
+    pub fn set_history(&mut self, history: &[u32]) -> Result<(), MarkovChainError> {
+        if let Some(&node_id) = history.iter().find(|id| !self.nodes.contains_key(id)) {
+            return Err(MarkovChainError::NodeDoesNotExistError { node_id });
+        }
+        self.history = history.to_vec();
+        Ok(())
+    }
+
+    pub fn get_current_node(&self) -> Option<u32> {
+        self.history.last().copied()
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<(), MarkovChainError> {
+        self.next_with_rng(&mut rand::thread_rng())
+    }
+
+    /// Samples the next node given the walk's history and appends it.
+    pub fn next_with_rng<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Result<(), MarkovChainError> {
+        let node_id = self
+            .get_current_node()
+            .ok_or(MarkovChainError::NoCurrentNodeError)?;
+
+        let edges = self.transition_edges(&self.history);
+        if edges.is_empty() {
+            return Err(MarkovChainError::NodeHasNoEdgesError { node_id });
+        }
+        let index = MarkovChain::sample_cumulative(&edges, rng)
+            .ok_or(MarkovChainError::TransitionFailedError { node_id })?;
+
+        // The current node is kept even when no context is used.
+        self.history.push(edges[index].to);
+        let keep = self.max_order.max(1);
+        if self.history.len() > keep {
+            self.history.drain(..self.history.len() - keep);
+        }
+        Ok(())
+    }
+
+    fn maximum_likelihood(row: Option<&HashMap<u32, f64>>) -> HashMap<u32, f64> {
+        let Some(row) = row else {
+            return HashMap::new();
+        };
+        let total: f64 = row.values().sum();
+        row.iter()
+            .map(|(&to, &count)| (to, count / total))
+            .collect()
+    }
+
+    fn katz(&self, context: &[u32], discount: f64, min_count: f64) -> HashMap<u32, f64> {
+        if context.is_empty() {
+            return Self::maximum_likelihood(self.counts.get(context));
+        }
+
+        let total = self.context_count(context);
+        if total == 0.0 || total < min_count {
+            return self.katz(&context[1..], discount, min_count);
+        }
+
+        let row = &self.counts[context];
+        let mut distribution: HashMap<u32, f64> = row
+            .iter()
+            .map(|(&to, &count)| (to, (count - discount).max(0.0) / total))
+            .collect();
+        let left_over = 1.0 - distribution.values().sum::<f64>();
+
+        let shorter = self.katz(&context[1..], discount, min_count);
+        let unseen_mass: f64 = shorter
+            .iter()
+            .filter(|(to, _)| !row.contains_key(to))
+            .map(|(_, p)| p)
+            .sum();
+
+        if unseen_mass > 0.0 {
+            for (to, p) in shorter {
+                if !row.contains_key(&to) {
+                    distribution.insert(to, left_over * p / unseen_mass);
+                }
+            }
+        } else {
+            // Nothing to back off to, so give the mass back to what was seen,
+            // or use the raw counts if a full discount left nothing.
+            let seen: f64 = distribution.values().sum();
+            if seen == 0.0 {
+                return Self::maximum_likelihood(Some(row));
+            }
+            for p in distribution.values_mut() {
+                *p /= seen;
+            }
+        }
+
+        distribution
+    }
+
+    fn kneser_ney(&self, context: &[u32], discount: f64, highest: bool) -> HashMap<u32, f64> {
+        let table = if highest {
+            &self.counts
+        } else {
+            &self.continuation_counts
+        };
+
+        if context.is_empty() {
+            return Self::maximum_likelihood(table.get(context));
+        }
+
+        let row = match table.get(context) {
+            Some(row) if !row.is_empty() => row,
+            _ => return self.kneser_ney(&context[1..], discount, false),
+        };
+        let total: f64 = row.values().sum();
+        let backoff_weight = discount * row.len() as f64 / total;
+
+        let mut distribution = self.kneser_ney(&context[1..], discount, false);
+        for p in distribution.values_mut() {
+            *p *= backoff_weight;
+        }
+        for (&to, &count) in row.iter() {
+            *distribution.entry(to).or_default() += (count - discount).max(0.0) / total;
+        }
+
+        distribution
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn probabilities(edges: &[Edge]) -> Vec<(u32, f32)> {
+        edges.iter().map(|edge| (edge.to, edge.weight)).collect()
+    }
+
+    fn assert_sums_to_one(edges: &[Edge]) {
+        let total: f32 = edges.iter().map(|edge| edge.weight).sum();
+        assert!((total - 1.0).abs() < 1e-5, "{:?}", edges);
+    }
+
+    #[test]
+    fn test_katz_backoff() {
+        let backoff = Backoff::Katz {
+            discount: 0.5,
+            min_count: 2,
+        };
+        let chain = VariableOrderChain::fit(2, backoff, [[1, 2, 3, 1, 2, 3, 2, 1]]).unwrap();
+
+        // (2) was followed by 3 twice and 1 once; its discounted third goes
+        // to 2, the only other id seen at all.
+        let shorter = probabilities(&chain.transition_edges(&[2]));
+        assert_eq!(shorter.len(), 3);
+        for ((_, actual), expected) in shorter.iter().zip([1.0 / 6.0, 1.0 / 3.0, 0.5]) {
+            assert!((actual - expected).abs() < 1e-6);
+        }
+
+        // (1, 2) was seen twice, always before 3, and the discounted quarter
+        // is shared between 1 and 2 in the proportions of (2).
+        let longer = probabilities(&chain.transition_edges(&[1, 2]));
+        for ((_, actual), expected) in longer.iter().zip([1.0 / 12.0, 1.0 / 6.0, 0.75]) {
+            assert!((actual - expected).abs() < 1e-6);
+        }
+
+        // (3, 2) was seen only once, so it backs off to (2) entirely.
+        assert_eq!(
+            chain.transition_edges(&[3, 2]),
+            chain.transition_edges(&[2])
+        );
+        assert_sums_to_one(&chain.transition_edges(&[9, 9]));
+    }
+
+    #[test]
+    fn test_interpolated_kneser_ney() {
+        let backoff = Backoff::InterpolatedKneserNey { discount: 0.75 };
+        let chain =
+            VariableOrderChain::fit(2, backoff, [&[1, 2, 3, 1, 2, 3, 2, 1][..], &[3, 3, 1]])
+                .unwrap();
+
+        for context in [&[1, 2][..], &[3, 2], &[2], &[], &[7, 3]] {
+            assert_sums_to_one(&chain.transition_edges(context));
+        }
+
+        // Interpolation leaves some mass on successors the context never saw.
+        let edges = chain.transition_edges(&[1, 2]);
+        assert_eq!(
+            edges.iter().map(|edge| edge.to).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert!(edges[2].weight > 0.5);
+    }
+
+    #[test]
+    fn test_walk() {
+        let backoff = Backoff::InterpolatedKneserNey { discount: 0.5 };
+        let mut chain = VariableOrderChain::fit(2, backoff, [[1, 2, 3, 1, 2, 3]]).unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+
+        assert_eq!(chain.next(), Err(MarkovChainError::NoCurrentNodeError));
+        assert_eq!(
+            chain.set_current_node(5),
+            Err(MarkovChainError::NodeDoesNotExistError { node_id: 5 })
+        );
+
+        chain.set_current_node(1).unwrap();
+        for _ in 0..20 {
+            chain.next_with_rng(&mut rng).unwrap();
+            assert!(chain.get_node(chain.get_current_node().unwrap()).is_some());
+        }
+        assert_eq!(chain.history.len(), 2);
+    }
+
+    #[test]
+    fn test_katz_with_full_discount() {
+        let backoff = Backoff::Katz {
+            discount: 1.0,
+            min_count: 1,
+        };
+        let mut chain = VariableOrderChain::fit(1, backoff, [[1, 1]]).unwrap();
+
+        assert_eq!(probabilities(&chain.transition_edges(&[1])), vec![(1, 1.0)]);
+        chain.set_current_node(1).unwrap();
+        assert_eq!(chain.next(), Ok(()));
+    }
+
+    #[test]
+    fn test_fit_with_invalid_discount() {
+        let backoff = Backoff::InterpolatedKneserNey { discount: 1.5 };
+        assert_eq!(
+            VariableOrderChain::fit(2, backoff, [[1, 2, 3]]).unwrap_err(),
+            MarkovChainError::InvalidParameterError {
+                name: "discount",
+                value: 1.5,
+                expected: "in [0, 1]",
+            }
+        );
+    }
+
+    #[test]
+    fn test_walk_without_context() {
+        let backoff = Backoff::Katz {
+            discount: 0.5,
+            min_count: 1,
+        };
+        let mut chain = VariableOrderChain::fit(0, backoff, [[1, 2, 3, 1, 2, 3]]).unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+
+        chain.set_current_node(1).unwrap();
+        for _ in 0..5 {
+            chain.next_with_rng(&mut rng).unwrap();
+            assert!(chain.get_current_node().is_some());
+        }
+        assert_eq!(chain.transition_edges(&[2])[0].from, 2);
+    }
+}
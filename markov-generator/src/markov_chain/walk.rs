@@ -0,0 +1,90 @@
+use crate::markov_chain::{action::GenericAction, GenericMarkovChain, MarkovChainError};
+use serde_json::Value;
+use std::hash::Hash;
+
+/// One transition taken during a walk.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step<I = u32, V = Value> {
+    pub from: I,
+    pub to: I,
+    pub weight: f32,
+    /// The `Exit` actions of the node that was left. Empty on a self-loop.
+    pub exit_actions: Vec<GenericAction<V>>,
+    /// The actions of the edge that was taken.
+    pub edge_actions: Vec<GenericAction<V>>,
+    /// The `Enter` actions of the destination node, or its `Stay` actions if
+    /// the step was a self-loop.
+    pub actions: Vec<GenericAction<V>>,
+}
+
+impl<I, V> Step<I, V> {
+    /// Every action of the step in firing order: exit, edge, then enter or
+    /// stay actions.
+    pub fn fired(&self) -> impl Iterator<Item = &GenericAction<V>> {
+        self.exit_actions
+            .iter()
+            .chain(&self.edge_actions)
+            .chain(&self.actions)
+    }
+}
+
+/// An iterator over the steps of a walk, created by
+/// [`GenericMarkovChain::walk`].
+///
+/// The walk ends after `max_steps` steps, after entering a terminal node, or
+/// when it reaches a node without outgoing edges. Any other error is yielded
+/// once and then ends the walk.
+#[derive(Debug)]
+pub struct Walk<'a, I = u32, V = Value> {
+    chain: &'a mut GenericMarkovChain<I, V>,
+    remaining: usize,
+    done: bool,
+}
+
+impl<'a, I, V> Walk<'a, I, V>
+where
+    I: Hash + Eq + Clone,
+    V: Clone,
+{
+    pub(crate) fn new(chain: &'a mut GenericMarkovChain<I, V>, max_steps: usize) -> Walk<'a, I, V> {
+        let done = chain
+            .get_current_node()
+            .is_none_or(|node_id| chain.is_terminal_node(node_id));
+
+        Walk {
+            chain,
+            remaining: max_steps,
+            done,
+        }
+    }
+}
+
+impl<I, V> Iterator for Walk<'_, I, V>
+where
+    I: Hash + Eq + Clone,
+    V: Clone,
+{
+    type Item = Result<Step<I, V>, MarkovChainError<I>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        match self.chain.step() {
+            Ok(step) => {
+                self.done = self.chain.is_terminal_node(step.to.clone());
+                Some(Ok(step))
+            }
+            Err(MarkovChainError::NodeHasNoEdgesError { .. }) => {
+                self.done = true;
+                None
+            }
+            Err(error) => {
+                self.done = true;
+                Some(Err(error))
+            }
+        }
+    }
+}